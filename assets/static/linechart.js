@@ -0,0 +1,268 @@
+// Time series line charts drawn on a canvas, served by the dashboard itself
+// so that it keeps working without internet access.
+//
+//   drawLineChart(element, {
+//     title: "Temperature (°C)",
+//     series: [{ label: "Kitchen", points: [[new Date(), 21.5], ...] }],
+//   });
+//
+// The chart fills the element, and is redrawn when it is resized.
+(function () {
+  const COLORS = ["#22d3ee", "#f97316", "#a3e635", "#e879f9", "#facc15", "#f87171"];
+  const TEXT = "#d1d5db";
+  const GRID = "#374151";
+  const BACKGROUND = "#1f2937";
+  const FONT = "12px ui-sans-serif, system-ui, sans-serif";
+  const MARGIN = { top: 12, right: 16, bottom: 28, left: 64 };
+  const LEGEND_HEIGHT = 24;
+  const TIME_STEPS = [
+    60e3, 5 * 60e3, 15 * 60e3, 30 * 60e3, 3600e3, 3 * 3600e3, 6 * 3600e3,
+    12 * 3600e3, 86400e3, 7 * 86400e3,
+  ];
+
+  const charts = new WeakMap();
+  const resizes = new ResizeObserver((entries) =>
+    entries.forEach((entry) => {
+      const options = charts.get(entry.target);
+      if (options) {
+        draw(entry.target, options);
+      }
+    })
+  );
+
+  window.drawLineChart = function (element, options) {
+    if (!charts.has(element)) {
+      resizes.observe(element);
+    }
+    charts.set(element, options);
+    draw(element, options);
+  };
+
+  function draw(element, options) {
+    let canvas = element.querySelector("canvas");
+    let tooltip = element.querySelector(".linechart-tooltip");
+    if (!canvas) {
+      element.style.position = "relative";
+      canvas = document.createElement("canvas");
+      canvas.style.display = "block";
+      tooltip = document.createElement("div");
+      tooltip.className = "linechart-tooltip";
+      Object.assign(tooltip.style, {
+        position: "absolute",
+        display: "none",
+        pointerEvents: "none",
+        background: "#f9fafb",
+        color: "#111827",
+        padding: "4px 8px",
+        borderRadius: "4px",
+        font: FONT,
+        whiteSpace: "nowrap",
+      });
+      element.append(canvas, tooltip);
+    }
+
+    const width = element.clientWidth;
+    const height = element.clientHeight;
+    const ratio = window.devicePixelRatio || 1;
+    canvas.width = width * ratio;
+    canvas.height = height * ratio;
+    canvas.style.width = `${width}px`;
+    canvas.style.height = `${height}px`;
+    const ctx = canvas.getContext("2d");
+    ctx.scale(ratio, ratio);
+    ctx.fillStyle = BACKGROUND;
+    ctx.fillRect(0, 0, width, height);
+    ctx.font = FONT;
+
+    const series = options.series.map((serie, index) => ({
+      label: serie.label,
+      color: COLORS[index % COLORS.length],
+      points: serie.points
+        .filter(([, value]) => value !== null && value !== undefined)
+        .sort(([a], [b]) => a - b),
+    }));
+    const points = series.flatMap((serie) => serie.points);
+    const plot = {
+      left: MARGIN.left,
+      top: MARGIN.top,
+      right: width - MARGIN.right,
+      bottom: height - MARGIN.bottom - LEGEND_HEIGHT,
+    };
+    if (points.length === 0 || plot.right <= plot.left || plot.bottom <= plot.top) {
+      ctx.fillStyle = TEXT;
+      ctx.textAlign = "center";
+      ctx.fillText("No data", width / 2, height / 2);
+      canvas.onmousemove = null;
+      return;
+    }
+
+    const times = points.map(([time]) => time.getTime());
+    const values = points.map(([, value]) => value);
+    const x = scale(Math.min(...times), Math.max(...times), plot.left, plot.right);
+    const yTicks = niceTicks(Math.min(...values), Math.max(...values));
+    const y = scale(yTicks[0], yTicks[yTicks.length - 1], plot.bottom, plot.top);
+
+    drawAxes(ctx, plot, x, y, yTicks, options.title);
+    series.forEach((serie) => {
+      ctx.strokeStyle = serie.color;
+      ctx.lineWidth = 2;
+      ctx.lineJoin = "round";
+      ctx.beginPath();
+      serie.points.forEach(([time, value], index) => {
+        const method = index === 0 ? "moveTo" : "lineTo";
+        ctx[method](x(time.getTime()), y(value));
+      });
+      ctx.stroke();
+      if (serie.points.length === 1) {
+        const [[time, value]] = serie.points;
+        ctx.fillStyle = serie.color;
+        ctx.fillRect(x(time.getTime()) - 2, y(value) - 2, 4, 4);
+      }
+    });
+    drawLegend(ctx, series, plot, height);
+
+    canvas.onmouseleave = () => {
+      tooltip.style.display = "none";
+    };
+    canvas.onmousemove = (event) => {
+      const mouseX = event.offsetX;
+      if (mouseX < plot.left || mouseX > plot.right) {
+        tooltip.style.display = "none";
+        return;
+      }
+      const nearest = series
+        .map((serie) => ({ serie, point: closest(serie.points, x.invert(mouseX)) }))
+        .filter(({ point }) => point);
+      if (nearest.length === 0) {
+        return;
+      }
+      const time = nearest[0].point[0];
+      tooltip.innerHTML = [
+        `<strong>${formatDateTime(time)}</strong>`,
+        ...nearest.map(
+          ({ serie, point }) =>
+            `<span style="color:${serie.color}">●</span> ${escape(serie.label)}: ${point[1]}`
+        ),
+      ].join("<br>");
+      tooltip.style.display = "block";
+      const left = Math.min(mouseX + 12, width - tooltip.offsetWidth);
+      tooltip.style.left = `${Math.max(0, left)}px`;
+      tooltip.style.top = `${plot.top}px`;
+    };
+  }
+
+  function drawAxes(ctx, plot, x, y, yTicks, title) {
+    ctx.fillStyle = TEXT;
+    ctx.strokeStyle = GRID;
+    ctx.lineWidth = 1;
+
+    ctx.textAlign = "right";
+    ctx.textBaseline = "middle";
+    const decimals = Math.max(0, -Math.floor(Math.log10(yTicks[1] - yTicks[0])));
+    yTicks.forEach((tick) => {
+      const tickY = Math.round(y(tick)) + 0.5;
+      ctx.beginPath();
+      ctx.moveTo(plot.left, tickY);
+      ctx.lineTo(plot.right, tickY);
+      ctx.stroke();
+      ctx.fillText(tick.toFixed(decimals), plot.left - 6, tickY);
+    });
+
+    const [start, end] = x.domain;
+    const step = TIME_STEPS.find((step) => (end - start) / step <= 6) || TIME_STEPS.at(-1);
+    const offset = new Date(start).getTimezoneOffset() * 60e3;
+    ctx.textAlign = "center";
+    ctx.textBaseline = "top";
+    for (let time = Math.ceil((start - offset) / step) * step + offset; time <= end; time += step) {
+      const tickX = Math.round(x(time)) + 0.5;
+      ctx.beginPath();
+      ctx.moveTo(tickX, plot.top);
+      ctx.lineTo(tickX, plot.bottom);
+      ctx.stroke();
+      const date = new Date(time);
+      ctx.fillText(step >= 86400e3 ? formatDate(date) : formatTime(date), tickX, plot.bottom + 6);
+    }
+
+    if (title) {
+      ctx.save();
+      ctx.translate(14, (plot.top + plot.bottom) / 2);
+      ctx.rotate(-Math.PI / 2);
+      ctx.textAlign = "center";
+      ctx.textBaseline = "middle";
+      ctx.fillText(title, 0, 0);
+      ctx.restore();
+    }
+  }
+
+  function drawLegend(ctx, series, plot, height) {
+    const widths = series.map((serie) => ctx.measureText(serie.label).width + 32);
+    let left = (plot.left + plot.right - widths.reduce((a, b) => a + b, 0)) / 2;
+    const middle = height - LEGEND_HEIGHT / 2;
+    ctx.textAlign = "left";
+    ctx.textBaseline = "middle";
+    series.forEach((serie, index) => {
+      ctx.fillStyle = serie.color;
+      ctx.fillRect(left, middle - 1, 16, 3);
+      ctx.fillStyle = TEXT;
+      ctx.fillText(serie.label, left + 20, middle);
+      left += widths[index];
+    });
+  }
+
+  // Linear mapping of [min, max] to [from, to], with its inverse.
+  function scale(min, max, from, to) {
+    const span = max - min || 1;
+    const map = (value) => from + ((value - min) / span) * (to - from);
+    map.invert = (position) => min + ((position - from) / (to - from)) * span;
+    map.domain = [min, max];
+    return map;
+  }
+
+  // Round ticks covering [min, max], e.g. 18, 19, 20, 21 for 18.2 to 20.7.
+  function niceTicks(min, max) {
+    if (min === max) {
+      min -= 1;
+      max += 1;
+    }
+    const rough = (max - min) / 5;
+    const magnitude = 10 ** Math.floor(Math.log10(rough));
+    const step = [1, 2, 5, 10].map((m) => m * magnitude).find((s) => s >= rough);
+    const ticks = [];
+    for (let tick = Math.floor(min / step) * step; tick < max + step; tick += step) {
+      ticks.push(Number(tick.toFixed(10)));
+      if (tick >= max) {
+        break;
+      }
+    }
+    return ticks;
+  }
+
+  // Point of `points` (sorted by time) closest to `time`.
+  function closest(points, time) {
+    let best = null;
+    points.forEach((point) => {
+      if (!best || Math.abs(point[0] - time) < Math.abs(best[0] - time)) {
+        best = point;
+      }
+    });
+    return best;
+  }
+
+  function formatTime(date) {
+    return date.toLocaleTimeString([], { hour: "2-digit", minute: "2-digit" });
+  }
+
+  function formatDate(date) {
+    return date.toLocaleDateString([], { day: "numeric", month: "short" });
+  }
+
+  function formatDateTime(date) {
+    return `${formatDate(date)} ${formatTime(date)}`;
+  }
+
+  function escape(text) {
+    const element = document.createElement("span");
+    element.textContent = text;
+    return element.innerHTML;
+  }
+})();
@@ -0,0 +1,554 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        Path, Query, State,
+    },
+    Json,
+};
+use chrono::{NaiveDateTime, TimeDelta};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast::error::RecvError, time::MissedTickBehavior};
+
+use crate::{
+    config::{AlertKind, AlertRule, SensorConfig},
+    data::{
+        check_range, db_now, parse_limit, parse_timestamp_param, Page, SensorData,
+        DB_TIMESTAMP_FORMAT,
+    },
+    db,
+    error::AppError,
+    notify::Notifications,
+    state::AppState,
+};
+
+/// Alert raised by a rule, active until `resolved_at` is set.
+#[derive(Serialize, Clone)]
+pub struct Alert {
+    pub id: i64,
+    pub rule: String,
+    pub message: String,
+    /// Checked value when the alert was raised.
+    pub value: f64,
+    pub triggered_at: String,
+    pub resolved_at: Option<String>,
+    pub acknowledged_at: Option<String>,
+}
+
+/// Change of an alert, sent to the notification channels.
+#[derive(Clone)]
+pub struct AlertEvent {
+    pub state: AlertState,
+    pub alert: Alert,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Raised,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertState::Raised => "raised",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+/// Query parameters accepted by `/alerts/history`.
+///
+/// `before` is the id of the last alert of the previous page: several rules
+/// can fire on the same reading, so the timestamps are not unique.
+#[derive(Deserialize)]
+pub struct AlertHistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<u32>,
+    pub before: Option<i64>,
+}
+
+impl AlertRule {
+    /// Whether the rule fires on larger values, rather than on smaller ones.
+    fn fires_above(&self) -> bool {
+        match self.kind {
+            AlertKind::Above | AlertKind::NoData => true,
+            AlertKind::Below => false,
+            AlertKind::Rate => self.threshold.unwrap_or_default() > 0.0,
+        }
+    }
+
+    fn limit(&self) -> f64 {
+        match self.kind {
+            AlertKind::NoData => self.window_secs as f64,
+            _ => self.threshold.unwrap_or_default(),
+        }
+    }
+
+    fn is_firing(&self, value: f64) -> bool {
+        if self.fires_above() {
+            value > self.limit()
+        } else {
+            value < self.limit()
+        }
+    }
+
+    /// Whether the value got back past the threshold by the hysteresis.
+    fn is_resolved(&self, value: f64) -> bool {
+        if self.fires_above() {
+            value <= self.limit() - self.hysteresis
+        } else {
+            value >= self.limit() + self.hysteresis
+        }
+    }
+
+    fn message(&self, value: f64) -> String {
+        let measurement = self.measurement.as_deref().unwrap_or_default();
+        match self.kind {
+            AlertKind::Above => format!("{measurement} is {value:.1}, above {}", self.limit()),
+            AlertKind::Below => format!("{measurement} is {value:.1}, below {}", self.limit()),
+            AlertKind::Rate => format!(
+                "{measurement} changed by {value:+.1} in {} s (limit {:+})",
+                self.window_secs,
+                self.limit()
+            ),
+            AlertKind::NoData => format!("no sensor data for {value} s"),
+        }
+    }
+}
+
+/// Starts the task evaluating the rules: the value rules on every new
+/// sensor row, the `no-data` rules every `alerts.interval_secs`. The raised
+/// and resolved alerts are sent to `notifications`.
+pub fn spawn_engine(state: AppState, notifications: Notifications) {
+    if state.config.alerts.rules.is_empty() {
+        return;
+    }
+
+    let rules: Arc<[AlertRule]> = state.config.alerts.rules.clone().into();
+    let mut feed = state.sensor_feed.subscribe();
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(state.config.alerts.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            let result = tokio::select! {
+                reading = feed.recv() => match reading {
+                    Ok(reading) => {
+                        let rules = rules.clone();
+                        let config = state.config.clone();
+                        db::run(&state.writer, move |conn| {
+                            evaluate_reading(conn, &config.sensors, &rules, &reading)
+                        })
+                        .await
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        eprintln!("error: alert rules skipped {missed} sensor rows");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => {
+                    let rules = rules.clone();
+                    let now = db_now(&state.config.database);
+                    db::run(&state.writer, move |conn| evaluate_no_data(conn, &rules, now)).await
+                }
+            };
+            match result {
+                Ok(events) => {
+                    for event in events {
+                        notifications.send(&event);
+                    }
+                }
+                Err(err) => eprintln!("error: cannot evaluate alert rules: {err}"),
+            }
+        }
+    });
+}
+
+/// Lists the active alerts, newest first.
+pub async fn get_alerts(State(state): State<AppState>) -> Result<Json<Vec<Alert>>, AppError> {
+    let alerts = db::run(&state.db, query_active).await?;
+    Ok(Json(alerts))
+}
+
+/// Lists all the alerts, active or resolved, newest first.
+pub async fn get_alert_history(
+    State(state): State<AppState>,
+    query: Result<Query<AlertHistoryQuery>, QueryRejection>,
+) -> Result<Json<Page<Alert>>, AppError> {
+    let Query(query) = query?;
+    let from = parse_timestamp_param("from", query.from.as_deref())?;
+    let to = parse_timestamp_param("to", query.to.as_deref())?;
+    let limit = parse_limit(query.limit)?;
+    check_range(&from, &to)?;
+
+    let before = query.before;
+    let rows = db::run(&state.db, move |conn| {
+        query_history(conn, from, to, before, limit + 1)
+    })
+    .await?;
+
+    Ok(Json(Page::from_rows(rows, limit, |alert| {
+        alert.id.to_string()
+    })))
+}
+
+/// Marks an alert as acknowledged. Acknowledging it again keeps the time of
+/// the first acknowledgement.
+pub async fn acknowledge_alert(
+    State(state): State<AppState>,
+    path: Result<Path<i64>, PathRejection>,
+) -> Result<Json<Alert>, AppError> {
+    let Path(id) = path?;
+    let now = db_now(&state.config.database)
+        .format(DB_TIMESTAMP_FORMAT)
+        .to_string();
+    let alert = db::run(&state.writer, move |conn| {
+        conn.prepare_cached(
+            "UPDATE Alerts SET acknowledged_at = ?2 \
+             WHERE id = ?1 AND acknowledged_at IS NULL",
+        )?
+        .execute(params![id, now])?;
+        query_alert(conn, id)
+    })
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("no alert with id {id}")))?;
+
+    Ok(Json(alert))
+}
+
+/// Evaluates the value and rate rules against a new reading.
+fn evaluate_reading(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    rules: &[AlertRule],
+    reading: &SensorData,
+) -> rusqlite::Result<Vec<AlertEvent>> {
+    let Ok(at) = NaiveDateTime::parse_from_str(&reading.timestamp, DB_TIMESTAMP_FORMAT) else {
+        eprintln!("error: invalid timestamp in database {}", reading.timestamp);
+        return Ok(Vec::new());
+    };
+
+    let mut events = Vec::new();
+    for rule in rules {
+        let Some(sensor) = rule
+            .measurement
+            .as_deref()
+            .and_then(|name| sensors.iter().find(|sensor| sensor.name == name))
+        else {
+            continue;
+        };
+        // Missing or out of the valid range.
+        let Some(current) = reading.value(&sensor.name) else {
+            continue;
+        };
+        let value = match rule.kind {
+            AlertKind::Above | AlertKind::Below => current,
+            AlertKind::Rate => {
+                let since = at - TimeDelta::seconds(rule.window_secs as i64);
+                match query_oldest_since(conn, sensor, &format_timestamp(since))? {
+                    Some(old) => current - old,
+                    None => continue,
+                }
+            }
+            AlertKind::NoData => continue,
+        };
+        events.extend(apply_rule(conn, rule, value, at)?);
+    }
+    Ok(events)
+}
+
+/// Evaluates the `no-data` rules against the age of the newest row.
+fn evaluate_no_data(
+    conn: &Connection,
+    rules: &[AlertRule],
+    now: NaiveDateTime,
+) -> rusqlite::Result<Vec<AlertEvent>> {
+    if !rules.iter().any(|rule| rule.kind == AlertKind::NoData) {
+        return Ok(Vec::new());
+    }
+
+    let newest: Option<String> = conn
+        .prepare_cached("SELECT MAX(timestamp) FROM SensorData")?
+        .query_row([], |row| row.get(0))?;
+    // An empty table has never received data: nothing to watch yet.
+    let Some(newest) = newest else {
+        return Ok(Vec::new());
+    };
+    let Ok(newest) = NaiveDateTime::parse_from_str(&newest, DB_TIMESTAMP_FORMAT) else {
+        eprintln!("error: invalid timestamp in database {newest}");
+        return Ok(Vec::new());
+    };
+
+    let age = (now - newest).num_seconds() as f64;
+    let mut events = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.kind == AlertKind::NoData) {
+        events.extend(apply_rule(conn, rule, age, now)?);
+    }
+    Ok(events)
+}
+
+/// Raises or resolves the alert of `rule` given the checked value, returning
+/// the change if any.
+fn apply_rule(
+    conn: &Connection,
+    rule: &AlertRule,
+    value: f64,
+    at: NaiveDateTime,
+) -> rusqlite::Result<Option<AlertEvent>> {
+    let active: Option<i64> = conn
+        .prepare_cached("SELECT id FROM Alerts WHERE rule = ?1 AND resolved_at IS NULL")?
+        .query_row([&rule.name], |row| row.get(0))
+        .optional()?;
+
+    let (id, state) = match active {
+        Some(id) if rule.is_resolved(value) => {
+            conn.prepare_cached("UPDATE Alerts SET resolved_at = ?2 WHERE id = ?1")?
+                .execute(params![id, format_timestamp(at)])?;
+            (id, AlertState::Resolved)
+        }
+        Some(_) => return Ok(None),
+        None if rule.is_firing(value) => {
+            let cooldown_start =
+                format_timestamp(at - TimeDelta::seconds(rule.cooldown_secs as i64));
+            let cooling_down = conn
+                .prepare_cached("SELECT 1 FROM Alerts WHERE rule = ?1 AND triggered_at > ?2")?
+                .exists(params![rule.name, cooldown_start])?;
+            if cooling_down {
+                return Ok(None);
+            }
+            conn.prepare_cached(
+                "INSERT INTO Alerts (rule, message, value, triggered_at) \
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![
+                rule.name,
+                rule.message(value),
+                value,
+                format_timestamp(at)
+            ])?;
+            (conn.last_insert_rowid(), AlertState::Raised)
+        }
+        None => return Ok(None),
+    };
+
+    Ok(query_alert(conn, id)?.map(|alert| AlertEvent { state, alert }))
+}
+
+/// Returns the oldest valid value of the sensor since `since`.
+fn query_oldest_since(
+    conn: &Connection,
+    sensor: &SensorConfig,
+    since: &str,
+) -> rusqlite::Result<Option<f64>> {
+    let Some(value) = sensor.value_sql() else {
+        return Ok(None);
+    };
+    conn.prepare_cached(&format!(
+        "SELECT {value} FROM SensorData \
+         WHERE timestamp >= ?1 AND {value} IS NOT NULL \
+         ORDER BY timestamp LIMIT 1"
+    ))?
+    .query_row([since], |row| row.get(0))
+    .optional()
+}
+
+const ALERT_COLUMNS: &str = "id, rule, message, value, triggered_at, resolved_at, acknowledged_at";
+
+fn alert_from_row(row: &rusqlite::Row) -> rusqlite::Result<Alert> {
+    Ok(Alert {
+        id: row.get(0)?,
+        rule: row.get(1)?,
+        message: row.get(2)?,
+        value: row.get(3)?,
+        triggered_at: row.get(4)?,
+        resolved_at: row.get(5)?,
+        acknowledged_at: row.get(6)?,
+    })
+}
+
+fn query_alert(conn: &Connection, id: i64) -> rusqlite::Result<Option<Alert>> {
+    conn.prepare_cached(&format!("SELECT {ALERT_COLUMNS} FROM Alerts WHERE id = ?1"))?
+        .query_row([id], alert_from_row)
+        .optional()
+}
+
+fn query_active(conn: &Connection) -> rusqlite::Result<Vec<Alert>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {ALERT_COLUMNS} FROM Alerts WHERE resolved_at IS NULL ORDER BY id DESC"
+    ))?;
+    let rows = stmt.query_map([], alert_from_row)?;
+    rows.collect()
+}
+
+fn query_history(
+    conn: &Connection,
+    from: Option<String>,
+    to: Option<String>,
+    before: Option<i64>,
+    limit: u32,
+) -> rusqlite::Result<Vec<Alert>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {ALERT_COLUMNS} FROM Alerts \
+         WHERE (?1 IS NULL OR triggered_at >= ?1) \
+           AND (?2 IS NULL OR triggered_at <= ?2) \
+           AND (?3 IS NULL OR id < ?3) \
+         ORDER BY id DESC \
+         LIMIT ?4"
+    ))?;
+    let rows = stmt.query_map(params![from, to, before, limit], alert_from_row)?;
+    rows.collect()
+}
+
+fn format_timestamp(timestamp: NaiveDateTime) -> String {
+    timestamp.format(DB_TIMESTAMP_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn sensors() -> Vec<SensorConfig> {
+        Config::default().sensors
+    }
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE SensorData (
+                 timestamp TEXT,
+                 bmp280_temperature REAL,
+                 bmp280_pressure REAL,
+                 htu21d_temperature REAL,
+                 htu21d_humidity REAL
+             );",
+        )
+        .unwrap();
+        db::create_schema(&conn).unwrap();
+        conn
+    }
+
+    fn humidity_rule() -> AlertRule {
+        AlertRule {
+            name: "basement-humidity".to_string(),
+            kind: AlertKind::Above,
+            measurement: Some("htu21d_humidity".to_string()),
+            threshold: Some(70.0),
+            window_secs: 600,
+            hysteresis: 2.0,
+            cooldown_secs: 3600,
+        }
+    }
+
+    fn reading(timestamp: &str, humidity: f64) -> SensorData {
+        SensorData {
+            timestamp: timestamp.to_string(),
+            values: [
+                ("bmp280_temp", 20.0),
+                ("bmp280_pressure", 1013.0),
+                ("htu21d_temp", 20.0),
+                ("htu21d_humidity", humidity),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Some(value)))
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn alert_is_resolved_past_the_hysteresis() {
+        let conn = test_db();
+        let rules = [humidity_rule()];
+
+        evaluate_reading(
+            &conn,
+            &sensors(),
+            &rules,
+            &reading("2026-10-16 10:00:00", 72.0),
+        )
+        .unwrap();
+        assert_eq!(query_active(&conn).unwrap().len(), 1);
+
+        // Below the threshold, but not by the hysteresis.
+        evaluate_reading(
+            &conn,
+            &sensors(),
+            &rules,
+            &reading("2026-10-16 10:01:00", 69.0),
+        )
+        .unwrap();
+        assert_eq!(query_active(&conn).unwrap().len(), 1);
+
+        let events = evaluate_reading(
+            &conn,
+            &sensors(),
+            &rules,
+            &reading("2026-10-16 10:02:00", 67.5),
+        )
+        .unwrap();
+        assert!(query_active(&conn).unwrap().is_empty());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Resolved);
+
+        let history = query_history(&conn, None, None, None, 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            history[0].resolved_at.as_deref(),
+            Some("2026-10-16 10:02:00")
+        );
+    }
+
+    #[test]
+    fn cooldown_delays_the_next_alert() {
+        let conn = test_db();
+        let rules = [humidity_rule()];
+
+        for (timestamp, humidity) in [
+            ("2026-10-16 10:00:00", 72.0),
+            ("2026-10-16 10:10:00", 60.0),
+            ("2026-10-16 10:20:00", 72.0),
+            ("2026-10-16 10:30:00", 60.0),
+            ("2026-10-16 11:05:00", 72.0),
+        ] {
+            evaluate_reading(&conn, &sensors(), &rules, &reading(timestamp, humidity)).unwrap();
+        }
+
+        let history = query_history(&conn, None, None, None, 10).unwrap();
+        let triggered: Vec<_> = history.iter().map(|alert| &alert.triggered_at).collect();
+        assert_eq!(triggered, ["2026-10-16 11:05:00", "2026-10-16 10:00:00"]);
+    }
+
+    #[test]
+    fn no_data_rule_fires_on_old_rows() {
+        let conn = test_db();
+        conn.execute(
+            "INSERT INTO SensorData VALUES ('2026-10-16 10:00:00', 20, 1013, 20, 50)",
+            [],
+        )
+        .unwrap();
+        let rules = [AlertRule {
+            name: "logger-down".to_string(),
+            kind: AlertKind::NoData,
+            measurement: None,
+            threshold: None,
+            window_secs: 900,
+            hysteresis: 0.0,
+            cooldown_secs: 0,
+        }];
+        let at = |timestamp| NaiveDateTime::parse_from_str(timestamp, DB_TIMESTAMP_FORMAT).unwrap();
+
+        evaluate_no_data(&conn, &rules, at("2026-10-16 10:10:00")).unwrap();
+        assert!(query_active(&conn).unwrap().is_empty());
+
+        evaluate_no_data(&conn, &rules, at("2026-10-16 10:20:00")).unwrap();
+        let active = query_active(&conn).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].message, "no sensor data for 1200 s");
+    }
+}
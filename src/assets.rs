@@ -0,0 +1,209 @@
+use std::{
+    borrow::Cow,
+    io::ErrorKind,
+    path::{Component, Path as FsPath},
+};
+
+use axum::{
+    extract::{rejection::PathRejection, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::{config::AssetsConfig, error::AppError, state::AppState, templates};
+
+/// Pages are checked on every load, so that an upgrade shows up at once.
+const PAGE_CACHE_CONTROL: &str = "no-cache";
+/// Static files built into the binary only change with it.
+const STATIC_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// The `templates` and `static` folders, built into the binary so that it
+/// is a complete deployment on its own.
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Embedded;
+
+/// Content of an asset, with the hash used as its `ETag`.
+#[derive(Clone)]
+pub struct Asset {
+    pub data: Cow<'static, [u8]>,
+    pub hash: [u8; 32],
+}
+
+impl Asset {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            hash: Sha256::digest(&data).into(),
+            data: Cow::Owned(data),
+        }
+    }
+}
+
+/// Loads an asset from `assets.dir` if set, or from the copies built into
+/// the binary. Returns `None` if there is no such file.
+pub async fn load(config: &AssetsConfig, path: &str) -> Result<Option<Asset>, AppError> {
+    let Some(dir) = &config.dir else {
+        return Ok(Embedded::get(path).map(|file| Asset {
+            hash: file.metadata.sha256_hash(),
+            data: file.data,
+        }));
+    };
+
+    // Only plain names, so that `..` can't escape the directory.
+    let relative = FsPath::new(path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Ok(None);
+    }
+    let path = dir.join(relative);
+    match fs::read(&path).await {
+        Ok(data) => Ok(Some(Asset::new(data))),
+        Err(err) if matches!(err.kind(), ErrorKind::NotFound | ErrorKind::IsADirectory) => Ok(None),
+        Err(err) => Err(AppError::Internal(format!(
+            "cannot read {}: {err}",
+            path.display()
+        ))),
+    }
+}
+
+/// Serves a page rendered from the `templates` folder. With `assets.dir`,
+/// it is rendered again on every request like the static files are read
+/// again, the others being rendered once at startup.
+pub async fn page(state: &AppState, headers: &HeaderMap, name: &str) -> Result<Response, AppError> {
+    let asset = if state.config.assets.dir.is_some() {
+        templates::render_page(&state.config, name)
+            .await
+            .map_err(AppError::Internal)?
+    } else {
+        state
+            .pages
+            .get(name)
+            .ok_or_else(|| AppError::Internal(format!("missing page {name}")))?
+    };
+    Ok(respond(asset, name, headers, PAGE_CACHE_CONTROL))
+}
+
+/// Serves the files of the `static` folder under `/static/`.
+pub async fn static_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    path: Result<Path<String>, PathRejection>,
+) -> Result<Response, AppError> {
+    let Path(path) = path?;
+    let asset = load(&state.config.assets, &format!("static/{path}"))
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("no static file {path}")))?;
+    let cache_control = if state.config.assets.dir.is_some() {
+        PAGE_CACHE_CONTROL
+    } else {
+        STATIC_CACHE_CONTROL
+    };
+    Ok(respond(asset, &path, &headers, cache_control))
+}
+
+/// Returns the asset with its `Content-Type`, `ETag` and `Cache-Control`, or
+/// an empty 304 if the browser already has this version.
+fn respond(asset: Asset, path: &str, headers: &HeaderMap, cache_control: &'static str) -> Response {
+    let etag: String = asset.hash[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let etag = format!("\"{etag}\"");
+    let cached = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, cache_control.to_string()),
+    ];
+    if is_current(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cached).into_response();
+    }
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let content_type = if mime.type_() == mime_guess::mime::TEXT {
+        format!("{mime}; charset=utf-8")
+    } else {
+        mime.to_string()
+    };
+    ([(header::CONTENT_TYPE, content_type)], cached, asset.data).into_response()
+}
+
+/// Whether the `If-None-Match` header of the request lists `etag`.
+fn is_current(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, http::HeaderValue};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pages_are_built_in() {
+        let config = AssetsConfig::default();
+        let asset = load(&config, "templates/base.html").await.unwrap().unwrap();
+        assert!(asset.data.starts_with(b"<!DOCTYPE html>"));
+
+        assert!(load(&config, "templates/missing.html")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn assets_dir_cannot_be_escaped() {
+        let config = AssetsConfig {
+            dir: Some(env!("CARGO_MANIFEST_DIR").into()),
+        };
+        assert!(load(&config, "assets/templates/index.html")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(load(&config, "assets/../Cargo.toml")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(load(&config, "/etc/hostname").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn unchanged_assets_are_not_sent_again() {
+        let asset = || Asset {
+            data: Cow::Borrowed(b"body { color: red; }"),
+            hash: [0xab; 32],
+        };
+
+        let response = respond(asset(), "app.css", &HeaderMap::new(), STATIC_CACHE_CONTROL);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            STATIC_CACHE_CONTROL
+        );
+        let etag = response.headers()[header::ETAG].clone();
+        assert_eq!(etag, format!("\"{}\"", "ab".repeat(16)).as_str());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"old\""));
+        headers.append(header::IF_NONE_MATCH, etag);
+        let response = respond(asset(), "app.css", &headers, STATIC_CACHE_CONTROL);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,757 @@
+use std::{io::IsTerminal, sync::LazyLock};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, Request, State,
+    },
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{TimeDelta, Utc};
+use clap::ValueEnum;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    assets,
+    config::UsersCommand,
+    data::DB_TIMESTAMP_FORMAT,
+    db::{self, DbPool},
+    error::AppError,
+    state::AppState,
+};
+
+/// Cookie holding the session token of the browsers.
+const SESSION_COOKIE: &str = "session";
+/// Minimum length of the passwords, in characters.
+const MIN_PASSWORD_LEN: usize = 8;
+/// Maximum length of the usernames and API token names.
+const MAX_NAME_LEN: usize = 64;
+/// Number of random bytes of the session and API tokens.
+const TOKEN_BYTES: usize = 32;
+
+/// Hash checked against the passwords of unknown users, so that a failed
+/// login takes as long whether the username exists or not.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| hash_password("not a password"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Can read everything, and manage its own API tokens.
+    ReadOnly,
+    /// Can also acknowledge alerts and manage the accounts.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read-only",
+            Role::Admin => "admin",
+        }
+    }
+
+    fn parse(role: &str) -> Option<Self> {
+        match role {
+            "read-only" => Some(Role::ReadOnly),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Account making the request, added to the request extensions by
+/// [`require_login`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+/// API token, as listed by `/auth/tokens`. The token itself is only shown
+/// once, when it is created.
+#[derive(Debug, Serialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct NewApiToken {
+    #[serde(flatten)]
+    pub info: ApiToken,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct NewToken {
+    pub name: String,
+}
+
+/// Routes of the accounts, only served when the authentication is enabled.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/login", get(login_page).post(login))
+        .route("/logout", post(logout))
+        .route("/auth/me", get(me))
+        .route("/auth/tokens", get(get_tokens).post(create_token))
+        .route("/auth/tokens/{id}", delete(delete_token))
+        .route("/admin/users", get(get_users).post(create_user))
+        .route("/admin/users/{username}", delete(delete_user))
+}
+
+/// Middleware requiring an account for every route but the login, the
+/// static files and the device readings, which have their own API keys.
+///
+/// The account is taken from the `Authorization: Bearer` API token, or from
+/// the session cookie of the browsers. The dashboard page redirects to the
+/// login page without one.
+pub async fn require_login(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if matches!(path, "/login" | "/logout" | "/api/readings") || path.starts_with("/static/") {
+        return next.run(request).await;
+    }
+
+    let user = match current_user(&state, request.headers()).await {
+        Ok(Some(user)) => user,
+        Ok(None) if path == "/" => return Redirect::to("/login").into_response(),
+        Ok(None) => return AppError::Unauthorized("login required".to_string()).into_response(),
+        Err(err) => return err.into_response(),
+    };
+    if !is_allowed(user.role, request.method(), path) {
+        return AppError::Forbidden(format!("{} is a read-only account", user.username))
+            .into_response();
+    }
+    request.extensions_mut().insert(user);
+    next.run(request).await
+}
+
+/// Read-only accounts may only read, besides managing their API tokens.
+fn is_allowed(role: Role, method: &Method, path: &str) -> bool {
+    match role {
+        Role::Admin => true,
+        Role::ReadOnly => {
+            !path.starts_with("/admin/")
+                && (matches!(*method, Method::GET | Method::HEAD) || path.starts_with("/auth/"))
+        }
+    }
+}
+
+async fn current_user(state: &AppState, headers: &HeaderMap) -> Result<Option<User>, AppError> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::Unauthorized("expected a Bearer API token".to_string()))?;
+        let token_hash = hash_token(token.trim());
+        return db::run(&state.db, move |conn| query_token_user(conn, &token_hash))
+            .await?
+            .map(Some)
+            .ok_or_else(|| AppError::Unauthorized("invalid API token".to_string()));
+    }
+
+    let Some(token) = session_token(headers) else {
+        return Ok(None);
+    };
+    let token_hash = hash_token(token);
+    let now = utc_now();
+    Ok(db::run(&state.db, move |conn| {
+        query_session_user(conn, &token_hash, &now)
+    })
+    .await?)
+}
+
+/// Returns the value of the session cookie, among the others.
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == SESSION_COOKIE).then_some(value)
+        })
+}
+
+/// Cookie setting the session token, only sent back over HTTPS when it is
+/// enabled.
+fn session_cookie(token: &str, max_age_secs: u64, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!(
+        "{SESSION_COOKIE}={token}; Path=/; Max-Age={max_age_secs}; HttpOnly; SameSite=Lax{secure}"
+    )
+}
+
+pub async fn login_page(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    assets::page(&state, &headers, "login.html").await
+}
+
+/// Checks the credentials and opens a session, returning the account.
+pub async fn login(
+    State(state): State<AppState>,
+    body: Result<Json<Credentials>, JsonRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Json(Credentials { username, password }) = body?;
+    let account = db::run(&state.db, move |conn| query_account(conn, &username)).await?;
+    let user = tokio::task::spawn_blocking(move || match account {
+        Some((user, hash)) if verify_password(&hash, &password) => Some(user),
+        Some(_) => None,
+        None => {
+            verify_password(&DUMMY_HASH, &password);
+            None
+        }
+    })
+    .await
+    .map_err(|err| AppError::Internal(format!("password check failed: {err}")))?
+    .ok_or_else(|| AppError::Unauthorized("invalid username or password".to_string()))?;
+
+    let token = new_token();
+    let token_hash = hash_token(&token);
+    let ttl = state.config.auth.session_ttl_secs;
+    let user_id = user.id;
+    db::run(&state.writer, move |conn| {
+        create_session(conn, user_id, &token_hash, ttl)
+    })
+    .await?;
+
+    Ok((
+        [(
+            header::SET_COOKIE,
+            session_cookie(&token, ttl, state.config.tls.enabled),
+        )],
+        Json(user),
+    ))
+}
+
+/// Closes the session of the browser, if any, and clears its cookie.
+pub async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(token) = session_token(&headers) {
+        let token_hash = hash_token(token);
+        db::run(&state.writer, move |conn| {
+            conn.execute("DELETE FROM sessions WHERE token_hash = ?1", [token_hash])
+        })
+        .await?;
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(
+            header::SET_COOKIE,
+            session_cookie("", 0, state.config.tls.enabled),
+        )],
+    ))
+}
+
+pub async fn me(Extension(user): Extension<User>) -> Json<User> {
+    Json(user)
+}
+
+pub async fn get_tokens(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ApiToken>>, AppError> {
+    let tokens = db::run(&state.db, move |conn| query_tokens(conn, user.id)).await?;
+    Ok(Json(tokens))
+}
+
+/// Creates an API token for the account, returned along with its details.
+pub async fn create_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    body: Result<Json<NewToken>, JsonRejection>,
+) -> Result<(StatusCode, Json<NewApiToken>), AppError> {
+    let Json(NewToken { name }) = body?;
+    check_name("token name", &name).map_err(AppError::BadRequest)?;
+
+    let token = new_token();
+    let token_hash = hash_token(&token);
+    let created_at = utc_now();
+    let token_name = name.clone();
+    let info = db::run(&state.writer, move |conn| {
+        insert_token(conn, user.id, &token_name, &token_hash, &created_at)
+    })
+    .await?
+    .ok_or_else(|| AppError::BadRequest(format!("an API token named {name:?} already exists")))?;
+
+    Ok((StatusCode::CREATED, Json(NewApiToken { info, token })))
+}
+
+pub async fn delete_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    path: Result<Path<i64>, PathRejection>,
+) -> Result<StatusCode, AppError> {
+    let Path(id) = path?;
+    let deleted = db::run(&state.writer, move |conn| {
+        conn.execute(
+            "DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2",
+            params![id, user.id],
+        )
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(AppError::NotFound(format!("no API token with id {id}")));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_users(State(state): State<AppState>) -> Result<Json<Vec<User>>, AppError> {
+    let users = db::run(&state.db, query_users).await?;
+    Ok(Json(users))
+}
+
+pub async fn create_user(
+    State(state): State<AppState>,
+    body: Result<Json<NewUser>, JsonRejection>,
+) -> Result<(StatusCode, Json<User>), AppError> {
+    let Json(NewUser {
+        username,
+        password,
+        role,
+    }) = body?;
+    check_name("username", &username).map_err(AppError::BadRequest)?;
+    check_password(&password).map_err(AppError::BadRequest)?;
+
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+        .await
+        .map_err(|err| AppError::Internal(format!("password hashing failed: {err}")))?;
+    let created_at = utc_now();
+    let name = username.clone();
+    let user = db::run(&state.writer, move |conn| {
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO users (username, password_hash, role, created_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![name, password_hash, role.as_str(), created_at],
+        )?;
+        Ok((inserted > 0).then(|| User {
+            id: conn.last_insert_rowid(),
+            username: name,
+            role,
+        }))
+    })
+    .await?
+    .ok_or_else(|| AppError::BadRequest(format!("user {username:?} already exists")))?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    path: Result<Path<String>, PathRejection>,
+) -> Result<StatusCode, AppError> {
+    let Path(username) = path?;
+    if username == user.username {
+        return Err(AppError::BadRequest(
+            "cannot delete your own account".to_string(),
+        ));
+    }
+    let name = username.clone();
+    if !db::run(&state.writer, move |conn| remove_user(conn, &name)).await? {
+        return Err(AppError::NotFound(format!("no user named {username:?}")));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Runs a `users` command of the command line.
+pub async fn run_command(writer: &DbPool, command: UsersCommand) -> Result<(), String> {
+    match command {
+        UsersCommand::Set { username, role } => {
+            check_name("username", &username)?;
+            let password = read_password()?;
+            let password_hash = hash_password(&password);
+            let created_at = utc_now();
+            db::run(writer, move |conn| {
+                set_user(conn, &username, &password_hash, role, &created_at)
+            })
+            .await
+            .map_err(|err| err.to_string())
+        }
+        UsersCommand::Remove { username } => {
+            let name = username.clone();
+            match db::run(writer, move |conn| remove_user(conn, &name)).await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(format!("no user named {username:?}")),
+                Err(err) => Err(err.to_string()),
+            }
+        }
+        UsersCommand::List => {
+            let users = db::run(writer, query_users)
+                .await
+                .map_err(|err| err.to_string())?;
+            for user in users {
+                println!("{}\t{}", user.username, user.role.as_str());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reads the password from the first line of the standard input.
+fn read_password() -> Result<String, String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+    }
+    let mut line = String::new();
+    stdin
+        .read_line(&mut line)
+        .map_err(|err| format!("cannot read the password: {err}"))?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    check_password(&password)?;
+    Ok(password)
+}
+
+/// Whether there is at least one account, to warn when the authentication
+/// is enabled without any.
+pub fn has_users(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.prepare("SELECT 1 FROM users")?.exists([])
+}
+
+fn check_name(what: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || name.chars().any(char::is_whitespace) {
+        return Err(format!(
+            "{what} must be 1 to {MAX_NAME_LEN} characters long, without spaces"
+        ));
+    }
+    Ok(())
+}
+
+fn check_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "passwords must be at least {MIN_PASSWORD_LEN} characters long"
+        ));
+    }
+    Ok(())
+}
+
+fn hash_password(password: &str) -> String {
+    let mut salt = [0; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).expect("16 bytes are a valid salt");
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("the default Argon2 parameters are valid")
+        .to_string()
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Random session or API token, in hexadecimal.
+fn new_token() -> String {
+    let mut bytes = [0; TOKEN_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Tokens are stored hashed, so that a copy of the database doesn't give
+/// access to the dashboard. Being random, they don't need a slow hash.
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Current time in the format of the account tables, which are in UTC
+/// whatever the setting of the sensor logger.
+fn utc_now() -> String {
+    Utc::now().format(DB_TIMESTAMP_FORMAT).to_string()
+}
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get("role")?;
+    Ok(User {
+        id: row.get("id")?,
+        username: row.get("username")?,
+        role: Role::parse(&role).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                format!("invalid role {role:?}").into(),
+            )
+        })?,
+    })
+}
+
+/// Returns the account with the hash of its password.
+fn query_account(conn: &Connection, username: &str) -> rusqlite::Result<Option<(User, String)>> {
+    conn.prepare_cached("SELECT id, username, role, password_hash FROM users WHERE username = ?1")?
+        .query_row([username], |row| {
+            Ok((user_from_row(row)?, row.get("password_hash")?))
+        })
+        .optional()
+}
+
+fn query_session_user(
+    conn: &Connection,
+    token_hash: &str,
+    now: &str,
+) -> rusqlite::Result<Option<User>> {
+    conn.prepare_cached(
+        "SELECT users.id, username, role FROM sessions \
+         JOIN users ON users.id = sessions.user_id \
+         WHERE token_hash = ?1 AND expires_at > ?2",
+    )?
+    .query_row([token_hash, now], user_from_row)
+    .optional()
+}
+
+fn query_token_user(conn: &Connection, token_hash: &str) -> rusqlite::Result<Option<User>> {
+    conn.prepare_cached(
+        "SELECT users.id, username, role FROM api_tokens \
+         JOIN users ON users.id = api_tokens.user_id \
+         WHERE token_hash = ?1",
+    )?
+    .query_row([token_hash], user_from_row)
+    .optional()
+}
+
+fn query_users(conn: &Connection) -> rusqlite::Result<Vec<User>> {
+    conn.prepare("SELECT id, username, role FROM users ORDER BY username")?
+        .query_map([], user_from_row)?
+        .collect()
+}
+
+fn query_tokens(conn: &Connection, user_id: i64) -> rusqlite::Result<Vec<ApiToken>> {
+    conn.prepare_cached(
+        "SELECT id, name, created_at FROM api_tokens WHERE user_id = ?1 ORDER BY name",
+    )?
+    .query_map([user_id], |row| {
+        Ok(ApiToken {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            created_at: row.get("created_at")?,
+        })
+    })?
+    .collect()
+}
+
+/// Opens a session of `ttl_secs`, dropping the expired ones on the way.
+fn create_session(
+    conn: &Connection,
+    user_id: i64,
+    token_hash: &str,
+    ttl_secs: u64,
+) -> rusqlite::Result<()> {
+    let now = Utc::now();
+    // The TTL is bounded by the configuration.
+    let expires_at = now + TimeDelta::seconds(ttl_secs as i64);
+    conn.execute(
+        "DELETE FROM sessions WHERE expires_at <= ?1",
+        [now.format(DB_TIMESTAMP_FORMAT).to_string()],
+    )?;
+    conn.execute(
+        "INSERT INTO sessions (token_hash, user_id, expires_at) VALUES (?1, ?2, ?3)",
+        params![
+            token_hash,
+            user_id,
+            expires_at.format(DB_TIMESTAMP_FORMAT).to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Inserts an API token, returning `None` if the account already has one of
+/// that name.
+fn insert_token(
+    conn: &Connection,
+    user_id: i64,
+    name: &str,
+    token_hash: &str,
+    created_at: &str,
+) -> rusqlite::Result<Option<ApiToken>> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO api_tokens (user_id, name, token_hash, created_at) \
+         VALUES (?1, ?2, ?3, ?4)",
+        params![user_id, name, token_hash, created_at],
+    )?;
+    Ok((inserted > 0).then(|| ApiToken {
+        id: conn.last_insert_rowid(),
+        name: name.to_string(),
+        created_at: created_at.to_string(),
+    }))
+}
+
+/// Creates or updates an account. Changing the password closes the sessions
+/// opened with the previous one.
+fn set_user(
+    conn: &Connection,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+    created_at: &str,
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO users (username, password_hash, role, created_at) \
+         VALUES (?1, ?2, ?3, ?4) \
+         ON CONFLICT (username) DO UPDATE \
+         SET password_hash = excluded.password_hash, role = excluded.role",
+        params![username, password_hash, role.as_str(), created_at],
+    )?;
+    tx.execute(
+        "DELETE FROM sessions \
+         WHERE user_id = (SELECT id FROM users WHERE username = ?1)",
+        [username],
+    )?;
+    tx.commit()
+}
+
+/// Deletes an account with its sessions and API tokens, returning whether it
+/// existed.
+fn remove_user(conn: &Connection, username: &str) -> rusqlite::Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    for table in ["sessions", "api_tokens"] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {table} \
+                 WHERE user_id = (SELECT id FROM users WHERE username = ?1)"
+            ),
+            [username],
+        )?;
+    }
+    let deleted = tx.execute("DELETE FROM users WHERE username = ?1", [username])?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::create_schema(&conn).unwrap();
+        conn
+    }
+
+    fn user(conn: &Connection, username: &str, role: Role) -> User {
+        set_user(conn, username, "hash", role, "2026-10-16 10:00:00").unwrap();
+        query_account(conn, username).unwrap().unwrap().0
+    }
+
+    #[test]
+    fn passwords_are_hashed_and_verified() {
+        let hash = hash_password("correct horse");
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(&hash, "correct horse"));
+        assert!(!verify_password(&hash, "wrong horse"));
+        assert!(!verify_password("garbage", "correct horse"));
+        assert_ne!(hash, hash_password("correct horse"));
+    }
+
+    #[test]
+    fn read_only_accounts_can_only_read() {
+        for (method, path, allowed) in [
+            (Method::GET, "/data", true),
+            (Method::HEAD, "/export", true),
+            (Method::POST, "/alerts/1/acknowledge", false),
+            (Method::POST, "/auth/tokens", true),
+            (Method::DELETE, "/auth/tokens/1", true),
+            (Method::GET, "/admin/users", false),
+        ] {
+            assert_eq!(
+                is_allowed(Role::ReadOnly, &method, path),
+                allowed,
+                "{method} {path}"
+            );
+            assert!(is_allowed(Role::Admin, &method, path));
+        }
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let conn = open();
+        let alice = user(&conn, "alice", Role::Admin);
+        create_session(&conn, alice.id, "abc", 3600).unwrap();
+
+        let now = utc_now();
+        let later = (Utc::now() + TimeDelta::hours(2))
+            .format(DB_TIMESTAMP_FORMAT)
+            .to_string();
+        assert_eq!(query_session_user(&conn, "abc", &now).unwrap(), Some(alice));
+        assert_eq!(query_session_user(&conn, "abc", &later).unwrap(), None);
+        assert_eq!(query_session_user(&conn, "def", &now).unwrap(), None);
+    }
+
+    #[test]
+    fn api_tokens_are_removed_with_their_account() {
+        let conn = open();
+        let bob = user(&conn, "bob", Role::ReadOnly);
+        let token = insert_token(&conn, bob.id, "grafana", "abc", "2026-10-16 10:00:00")
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.name, "grafana");
+        assert!(
+            insert_token(&conn, bob.id, "grafana", "def", "2026-10-16 10:00:00")
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(query_token_user(&conn, "abc").unwrap(), Some(bob));
+
+        assert!(remove_user(&conn, "bob").unwrap());
+        assert_eq!(query_token_user(&conn, "abc").unwrap(), None);
+        assert!(!remove_user(&conn, "bob").unwrap());
+    }
+
+    #[test]
+    fn session_cookie_is_found_among_others() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(
+            header::COOKIE,
+            HeaderValue::from_static("lang=fr; session=abc123; x=y"),
+        );
+
+        assert_eq!(session_token(&headers), Some("abc123"));
+        assert_eq!(session_token(&HeaderMap::new()), None);
+    }
+}
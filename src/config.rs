@@ -0,0 +1,1328 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use lettre::message::Mailbox;
+use serde::Deserialize;
+
+use crate::{
+    auth::Role, data::table_sensors, devices::LOCAL_DEVICE, metrics::RESERVED_NAMES,
+    notify::template,
+};
+
+/// Minimum length of the device API keys.
+const MIN_API_KEY_LEN: usize = 16;
+/// Maximum lifetime of the login sessions.
+const MAX_SESSION_TTL_SECS: u64 = 365 * 24 * 3600;
+
+/// Command-line flags. Each one can also be set through the environment
+/// variable shown in `--help`; flags take precedence over the environment,
+/// which takes precedence over the configuration file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path of the TOML configuration file
+    #[arg(long, env = "PI_HOME_DASHBOARD_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address the HTTP server listens on
+    #[arg(long, env = "PI_HOME_DASHBOARD_BIND")]
+    pub bind: Option<SocketAddr>,
+
+    /// Path of the SQLite database written by the sensor logger
+    #[arg(long, env = "PI_HOME_DASHBOARD_DB")]
+    pub db: Option<PathBuf>,
+
+    /// Path of the SQLite database holding the tables of the dashboard
+    #[arg(long, env = "PI_HOME_DASHBOARD_DASHBOARD_DB")]
+    pub dashboard_db: Option<PathBuf>,
+
+    /// Directory to read the templates and static files from, instead of
+    /// the copies built into the binary
+    #[arg(long, env = "PI_HOME_DASHBOARD_ASSETS_DIR")]
+    pub assets_dir: Option<PathBuf>,
+
+    /// Latitude used for the external weather
+    #[arg(
+        long,
+        env = "PI_HOME_DASHBOARD_LATITUDE",
+        allow_negative_numbers = true
+    )]
+    pub latitude: Option<f64>,
+
+    /// Longitude used for the external weather
+    #[arg(
+        long,
+        env = "PI_HOME_DASHBOARD_LONGITUDE",
+        allow_negative_numbers = true
+    )]
+    pub longitude: Option<f64>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Maintenance commands, run instead of the server.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the accounts of the dashboard
+    #[command(subcommand)]
+    Users(UsersCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UsersCommand {
+    /// Create an account, or change the password and role of an existing
+    /// one. The password is read from the standard input.
+    Set {
+        username: String,
+        #[arg(long, value_enum, default_value_t = Role::ReadOnly)]
+        role: Role,
+    },
+    /// Delete an account, with its sessions and API tokens
+    Remove { username: String },
+    /// List the accounts
+    List,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub assets: AssetsConfig,
+    pub dashboard: DashboardConfig,
+    pub weather: WeatherConfig,
+    pub alerts: AlertsConfig,
+    pub notifications: NotificationsConfig,
+    pub mqtt: MqttConfig,
+    pub auth: AuthConfig,
+    pub tls: TlsConfig,
+    pub devices: Vec<DeviceConfig>,
+    /// Registry of the measurements, defaulting to the BMP280 and HTU21D
+    /// columns of the sensor logger.
+    pub sensors: Vec<SensorConfig>,
+}
+
+/// Measurement of the sensor registry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SensorConfig {
+    /// Unique key of the measurement in the API responses and exports.
+    pub name: String,
+    /// Column of the `SensorData` table holding the values. Without it, the
+    /// entry only describes the measurements of that name reported by other
+    /// devices.
+    pub column: Option<String>,
+    /// Name shown on the dashboard, defaults to `name`.
+    pub label: Option<String>,
+    #[serde(default)]
+    pub unit: String,
+    /// Chart the measurement is drawn in, shared with the measurements of
+    /// the same kind (e.g. `temperature`). Defaults to `name`.
+    pub chart: Option<String>,
+    /// Bounds of the plausible values, the others being ignored as sensor
+    /// errors.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Name of the Prometheus gauge, without the `pi_home_` prefix. Defaults
+    /// to `sensor_<name>`.
+    pub metric: Option<String>,
+}
+
+/// Display name and room of a device, the local one being `local`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub id: String,
+    pub name: Option<String>,
+    /// Room of the device, used for the sensors it reports.
+    pub location: Option<String>,
+    /// Key authenticating the readings the device sends to
+    /// `POST /api/readings`, which it refuses without one.
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Database written by the sensor logger, only ever read.
+    pub path: PathBuf,
+    /// Database of the tables owned by the dashboard (accounts, alerts,
+    /// devices, weather history), created if missing.
+    pub dashboard_path: PathBuf,
+    /// Maximum number of read-only connections kept open.
+    pub pool_size: u32,
+    /// Interval between two checks for new rows, for `/data/stream`.
+    pub poll_interval_secs: u64,
+    /// Whether the sensor logger writes timestamps in UTC rather than in
+    /// local time.
+    pub utc_timestamps: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetsConfig {
+    /// Directory holding the `templates` and `static` folders, read on every
+    /// request instead of the copies built into the binary. Meant for
+    /// working on the pages without rebuilding.
+    pub dir: Option<PathBuf>,
+}
+
+/// What the pages show, passed to their templates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DashboardConfig {
+    /// Name of the site, used as the title of the pages.
+    pub title: String,
+    /// Place of the outdoor weather, e.g. `Paris`.
+    pub location: Option<String>,
+    /// Units the temperatures, wind speeds and pressures are shown in.
+    pub temperature_unit: TemperatureUnit,
+    pub wind_speed_unit: WindSpeedUnit,
+    pub pressure_unit: PressureUnit,
+    /// Measurements of the sensor registry shown on the dashboard, all of
+    /// them if empty.
+    pub sensors: Vec<String>,
+    /// Interval between two refreshes of the values shown.
+    pub refresh_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WindSpeedUnit {
+    #[serde(rename = "km/h")]
+    KilometersPerHour,
+    #[serde(rename = "m/s")]
+    MetersPerSecond,
+    #[serde(rename = "mph")]
+    MilesPerHour,
+    #[serde(rename = "kn")]
+    Knots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PressureUnit {
+    #[serde(rename = "hPa")]
+    Hectopascals,
+    #[serde(rename = "inHg")]
+    InchesOfMercury,
+    #[serde(rename = "mmHg")]
+    MillimetersOfMercury,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Providers to fetch the weather from, tried in order until one
+    /// succeeds.
+    pub providers: Vec<WeatherProviderKind>,
+    /// open-meteo forecast endpoint.
+    #[serde(alias = "api_url")]
+    pub open_meteo_url: String,
+    /// MET Norway locationforecast endpoint.
+    pub met_norway_url: String,
+    /// JSON fixture read by the `file` provider.
+    pub file_path: Option<PathBuf>,
+    /// How long a fetched weather reading is served before refreshing it.
+    pub cache_ttl_secs: u64,
+    /// Interval between two readings recorded in the database, 0 disables
+    /// the recording.
+    pub poll_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Whether to publish the readings to an MQTT broker.
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic of the sensor rows, published as JSON.
+    pub sensors_topic: String,
+    /// Topic of the current outdoor weather, published as JSON.
+    pub weather_topic: String,
+    /// Topic set to `online` while connected, and to `offline` by the broker
+    /// once the connection is lost.
+    pub availability_topic: String,
+    /// Interval between two publications of the weather, 0 disables them.
+    pub weather_interval_secs: u64,
+    /// Prefix of the Home Assistant discovery topics, empty to disable the
+    /// discovery.
+    pub discovery_prefix: String,
+    /// Topic filter of the readings sent by other devices, where a `+` level
+    /// stands for the device id. Empty to disable the ingestion.
+    pub ingest_topic: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Whether the dashboard requires an account, created with the `users`
+    /// command. The devices keep using their API keys.
+    pub enabled: bool,
+    /// How long a login lasts.
+    pub session_ttl_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Whether to serve HTTPS rather than plain HTTP on `server.bind`.
+    pub enabled: bool,
+    /// PEM certificate chain and private key, reloaded on `SIGHUP`. A
+    /// self-signed certificate is generated there if neither file exists.
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// Names and addresses of the generated certificate, defaulting to
+    /// `localhost` and the host name of the Pi.
+    pub self_signed_names: Vec<String>,
+    /// Address of a plain HTTP listener redirecting to HTTPS.
+    pub redirect_bind: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeatherProviderKind {
+    OpenMeteo,
+    MetNorway,
+    File,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Interval between two checks of the `no-data` rules.
+    pub interval_secs: u64,
+    pub rules: Vec<AlertRule>,
+}
+
+/// Rule raising an alert from the sensor readings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    /// Unique name of the rule, stored with its alerts.
+    pub name: String,
+    pub kind: AlertKind,
+    /// Name of the measurement checked by the rule, from the sensor
+    /// registry. Unused by `no-data` rules.
+    pub measurement: Option<String>,
+    /// Limit of `above` and `below` rules, or change over `window_secs` of
+    /// `rate` rules (negative for a fall).
+    pub threshold: Option<f64>,
+    /// Period over which `rate` rules measure the change, or without rows
+    /// after which `no-data` rules fire.
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Margin, in the unit of the checked value, by which it must get back
+    /// below (or above) the threshold for the alert to be resolved.
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// Minimum delay between two alerts raised by the rule.
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlertKind {
+    Above,
+    Below,
+    Rate,
+    NoData,
+}
+
+fn default_window_secs() -> u64 {
+    600
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Number of attempts made after a failed delivery.
+    pub retries: u32,
+    /// Delay before the first retry, doubled after each attempt.
+    pub retry_delay_secs: u64,
+    pub channels: Vec<NotificationChannel>,
+}
+
+/// Destination of the alert notifications.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationChannel {
+    /// Unique name of the channel, stored in the delivery log.
+    pub name: String,
+    pub kind: NotifierKind,
+    /// Webhook URL, ntfy topic URL or Gotify server URL.
+    pub url: Option<String>,
+    /// ntfy access token or Gotify application token.
+    pub token: Option<String>,
+    /// Templates of the notification title and body, see
+    /// [`crate::notify::template`].
+    #[serde(default = "default_title")]
+    pub title: String,
+    #[serde(default = "default_body")]
+    pub body: String,
+    pub smtp_host: Option<String>,
+    /// Defaults to the standard port of `smtp_security`.
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifierKind {
+    Webhook,
+    Email,
+    Ntfy,
+    Gotify,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS, port 587.
+    #[default]
+    Starttls,
+    /// TLS from the start, port 465.
+    Tls,
+    /// Unencrypted, port 25. Only meant for a relay on the local network.
+    None,
+}
+
+fn default_title() -> String {
+    "[{state}] {rule}".to_string()
+}
+
+fn default_body() -> String {
+    "{message}".to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            database: DatabaseConfig::default(),
+            assets: AssetsConfig::default(),
+            dashboard: DashboardConfig::default(),
+            weather: WeatherConfig::default(),
+            alerts: AlertsConfig::default(),
+            notifications: NotificationsConfig::default(),
+            mqtt: MqttConfig::default(),
+            auth: AuthConfig::default(),
+            tls: TlsConfig::default(),
+            devices: Vec::new(),
+            sensors: default_sensors(),
+        }
+    }
+}
+
+/// Columns written by the sensor logger of the Raspberry Pi, and the usual
+/// measurements of the other devices.
+fn default_sensors() -> Vec<SensorConfig> {
+    let sensor =
+        |name: &str, label: &str, unit: &str, chart: &str, min: f64, max: f64| SensorConfig {
+            name: name.to_string(),
+            column: None,
+            label: Some(label.to_string()),
+            unit: unit.to_string(),
+            chart: Some(chart.to_string()),
+            min: Some(min),
+            max: Some(max),
+            metric: None,
+        };
+    let column = |column: &str, metric: &str, sensor: SensorConfig| SensorConfig {
+        column: Some(column.to_string()),
+        metric: Some(metric.to_string()),
+        ..sensor
+    };
+    vec![
+        column(
+            "bmp280_temperature",
+            "bmp280_temperature_celsius",
+            sensor(
+                "bmp280_temp",
+                "BMP280 temperature",
+                "°C",
+                "temperature",
+                -40.0,
+                85.0,
+            ),
+        ),
+        column(
+            "bmp280_pressure",
+            "bmp280_pressure_hpa",
+            sensor(
+                "bmp280_pressure",
+                "Pressure",
+                "hPa",
+                "pressure",
+                300.0,
+                1100.0,
+            ),
+        ),
+        column(
+            "htu21d_temperature",
+            "htu21d_temperature_celsius",
+            sensor(
+                "htu21d_temp",
+                "HTU21D temperature",
+                "°C",
+                "temperature",
+                -40.0,
+                125.0,
+            ),
+        ),
+        column(
+            "htu21d_humidity",
+            "htu21d_humidity_percent",
+            sensor("htu21d_humidity", "Humidity", "%", "humidity", 0.0, 100.0),
+        ),
+        sensor(
+            "temperature",
+            "Temperature",
+            "°C",
+            "temperature",
+            -50.0,
+            100.0,
+        ),
+        sensor("humidity", "Humidity", "%", "humidity", 0.0, 100.0),
+        sensor("pressure", "Pressure", "hPa", "pressure", 300.0, 1100.0),
+        sensor("co2", "CO2", "ppm", "co2", 0.0, 10000.0),
+    ]
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            title: "Sensor Dashboard".to_string(),
+            location: None,
+            temperature_unit: TemperatureUnit::Celsius,
+            wind_speed_unit: WindSpeedUnit::KilometersPerHour,
+            pressure_unit: PressureUnit::Hectopascals,
+            sensors: Vec::new(),
+            refresh_secs: 30,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("/var/lib/pi-home-sensors_data/data.db"),
+            dashboard_path: PathBuf::from("/var/lib/pi-home-dashboard/dashboard.db"),
+            pool_size: 4,
+            poll_interval_secs: 2,
+            utc_timestamps: false,
+        }
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "pi-home-dashboard".to_string(),
+            username: None,
+            password: None,
+            sensors_topic: "pi-home-dashboard/sensors".to_string(),
+            weather_topic: "pi-home-dashboard/weather".to_string(),
+            availability_topic: "pi-home-dashboard/status".to_string(),
+            weather_interval_secs: 300,
+            discovery_prefix: "homeassistant".to_string(),
+            ingest_topic: String::new(),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            session_ttl_secs: 7 * 24 * 3600,
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: PathBuf::from("/var/lib/pi-home-dashboard/tls/cert.pem"),
+            key_path: PathBuf::from("/var/lib/pi-home-dashboard/tls/key.pem"),
+            self_signed_names: Vec::new(),
+            redirect_bind: None,
+        }
+    }
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_delay_secs: 10,
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        // Paris
+        Self {
+            latitude: 48.85,
+            longitude: 2.35,
+            providers: vec![WeatherProviderKind::OpenMeteo],
+            open_meteo_url: "https://api.open-meteo.com/v1/forecast".to_string(),
+            met_norway_url: "https://api.met.no/weatherapi/locationforecast/2.0/complete"
+                .to_string(),
+            file_path: None,
+            cache_ttl_secs: 300,
+            poll_interval_secs: 900,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => {
+                write!(f, "cannot read config file {}: {err}", path.display())
+            }
+            ConfigError::Parse(path, err) => {
+                write!(f, "invalid config file {}: {err}", path.display())
+            }
+            ConfigError::Invalid(msg) => write!(f, "invalid configuration: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Builds the configuration from the file given on the command line (if
+    /// any), then applies the environment and command-line overrides, and
+    /// validates the result.
+    pub fn load(cli: Cli) -> Result<Self, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        if let Some(bind) = cli.bind {
+            config.server.bind = bind;
+        }
+        if let Some(db) = cli.db {
+            config.database.path = db;
+        }
+        if let Some(dashboard_db) = cli.dashboard_db {
+            config.database.dashboard_path = dashboard_db;
+        }
+        if let Some(assets_dir) = cli.assets_dir {
+            config.assets.dir = Some(assets_dir);
+        }
+        if let Some(latitude) = cli.latitude {
+            config.weather.latitude = latitude;
+        }
+        if let Some(longitude) = cli.longitude {
+            config.weather.longitude = longitude;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.to_path_buf(), err))?;
+        toml::from_str(&content).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    /// Checks the settings that deserializing doesn't, and their
+    /// consistency.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.database.path.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid(
+                "database.path must not be empty".to_string(),
+            ));
+        }
+        if self.database.dashboard_path.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid(
+                "database.dashboard_path must not be empty".to_string(),
+            ));
+        }
+        if self.database.dashboard_path == self.database.path {
+            return Err(ConfigError::Invalid(
+                "database.dashboard_path must differ from database.path".to_string(),
+            ));
+        }
+        if self.database.pool_size == 0 {
+            return Err(ConfigError::Invalid(
+                "database.pool_size must be at least 1".to_string(),
+            ));
+        }
+        if self.database.poll_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "database.poll_interval_secs must be at least 1".to_string(),
+            ));
+        }
+        if let Some(dir) = &self.assets.dir {
+            if !dir.is_dir() {
+                return Err(ConfigError::Invalid(format!(
+                    "assets.dir {} is not a directory",
+                    dir.display()
+                )));
+            }
+        }
+        if self.dashboard.title.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "dashboard.title must not be empty".to_string(),
+            ));
+        }
+        if self.dashboard.refresh_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "dashboard.refresh_secs must be at least 1".to_string(),
+            ));
+        }
+        for name in &self.dashboard.sensors {
+            if !self.sensors.iter().any(|sensor| &sensor.name == name) {
+                return Err(ConfigError::Invalid(format!(
+                    "dashboard.sensors: {name:?} is not in the sensor registry"
+                )));
+            }
+        }
+        if !(-90.0..=90.0).contains(&self.weather.latitude) {
+            return Err(ConfigError::Invalid(format!(
+                "weather.latitude must be between -90 and 90, got {}",
+                self.weather.latitude
+            )));
+        }
+        if !(-180.0..=180.0).contains(&self.weather.longitude) {
+            return Err(ConfigError::Invalid(format!(
+                "weather.longitude must be between -180 and 180, got {}",
+                self.weather.longitude
+            )));
+        }
+        if self.weather.providers.is_empty() {
+            return Err(ConfigError::Invalid(
+                "weather.providers must list at least one provider".to_string(),
+            ));
+        }
+        for (name, url) in [
+            ("weather.open_meteo_url", &self.weather.open_meteo_url),
+            ("weather.met_norway_url", &self.weather.met_norway_url),
+        ] {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(ConfigError::Invalid(format!(
+                    "{name} must be an http:// or https:// URL, got {url}"
+                )));
+            }
+        }
+        if self.weather.providers.contains(&WeatherProviderKind::File) {
+            match &self.weather.file_path {
+                Some(path) if path.is_file() => {}
+                Some(path) => {
+                    return Err(ConfigError::Invalid(format!(
+                        "weather.file_path {} is not a file",
+                        path.display()
+                    )))
+                }
+                None => {
+                    return Err(ConfigError::Invalid(
+                        "weather.file_path is required by the `file` provider".to_string(),
+                    ))
+                }
+            }
+        }
+        if self.alerts.interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "alerts.interval_secs must be at least 1".to_string(),
+            ));
+        }
+        for (i, sensor) in self.sensors.iter().enumerate() {
+            sensor.validate()?;
+            if self.sensors[..i]
+                .iter()
+                .any(|other| other.name == sensor.name)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "sensor {:?} is defined twice",
+                    sensor.name
+                )));
+            }
+        }
+        // The columns are exported by /metrics under these names.
+        let metrics: Vec<_> = table_sensors(&self.sensors)
+            .map(|sensor| (sensor, sensor.metric_name()))
+            .collect();
+        for (i, (sensor, metric)) in metrics.iter().enumerate() {
+            if RESERVED_NAMES.contains(&metric.as_str())
+                || metrics[..i].iter().any(|(_, other)| other == metric)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "sensor {:?}: metric name {metric:?} is already used",
+                    sensor.name
+                )));
+            }
+        }
+        for (i, rule) in self.alerts.rules.iter().enumerate() {
+            rule.validate(&self.sensors)?;
+            if self.alerts.rules[..i]
+                .iter()
+                .any(|other| other.name == rule.name)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "alert rule {:?} is defined twice",
+                    rule.name
+                )));
+            }
+        }
+        for (i, channel) in self.notifications.channels.iter().enumerate() {
+            channel.validate()?;
+            if self.notifications.channels[..i]
+                .iter()
+                .any(|other| other.name == channel.name)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "notification channel {:?} is defined twice",
+                    channel.name
+                )));
+            }
+        }
+        if self.mqtt.enabled {
+            self.mqtt.validate()?;
+        }
+        if self.tls.enabled {
+            self.tls.validate(self.server.bind)?;
+        }
+        if !(1..=MAX_SESSION_TTL_SECS).contains(&self.auth.session_ttl_secs) {
+            return Err(ConfigError::Invalid(format!(
+                "auth.session_ttl_secs must be between 1 and {MAX_SESSION_TTL_SECS}"
+            )));
+        }
+        for (i, device) in self.devices.iter().enumerate() {
+            if device.id.is_empty() {
+                return Err(ConfigError::Invalid(
+                    "device ids must not be empty".to_string(),
+                ));
+            }
+            if self.devices[..i].iter().any(|other| other.id == device.id) {
+                return Err(ConfigError::Invalid(format!(
+                    "device {:?} is defined twice",
+                    device.id
+                )));
+            }
+            if let Some(api_key) = &device.api_key {
+                if device.id == LOCAL_DEVICE {
+                    return Err(ConfigError::Invalid(format!(
+                        "device {LOCAL_DEVICE:?} is the sensor logger and cannot have an `api_key`"
+                    )));
+                }
+                if api_key.len() < MIN_API_KEY_LEN {
+                    return Err(ConfigError::Invalid(format!(
+                        "device {:?}: `api_key` must be at least {MIN_API_KEY_LEN} characters long",
+                        device.id
+                    )));
+                }
+                if self.devices[..i]
+                    .iter()
+                    .any(|other| other.api_key.as_ref() == Some(api_key))
+                {
+                    return Err(ConfigError::Invalid(format!(
+                        "device {:?}: `api_key` is already used by another device",
+                        device.id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MqttConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.host.is_empty() {
+            return Err(ConfigError::Invalid(
+                "mqtt.host must not be empty".to_string(),
+            ));
+        }
+        if self.client_id.is_empty() {
+            return Err(ConfigError::Invalid(
+                "mqtt.client_id must not be empty".to_string(),
+            ));
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err(ConfigError::Invalid(
+                "mqtt.username and mqtt.password go together".to_string(),
+            ));
+        }
+        for (name, topic) in [
+            ("mqtt.sensors_topic", &self.sensors_topic),
+            ("mqtt.weather_topic", &self.weather_topic),
+            ("mqtt.availability_topic", &self.availability_topic),
+        ] {
+            check_topic(name, topic)?;
+        }
+        if !self.discovery_prefix.is_empty() {
+            check_topic("mqtt.discovery_prefix", &self.discovery_prefix)?;
+        }
+        if !self.ingest_topic.is_empty()
+            && (self
+                .ingest_topic
+                .split('/')
+                .filter(|level| *level == "+")
+                .count()
+                != 1
+                || self.ingest_topic.matches(['+', '#']).count() != 1)
+        {
+            return Err(ConfigError::Invalid(format!(
+                "mqtt.ingest_topic must contain exactly one `+` level for the device id, \
+                 and no `#`, got {:?}",
+                self.ingest_topic
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl TlsConfig {
+    fn validate(&self, bind: SocketAddr) -> Result<(), ConfigError> {
+        for (name, path) in [
+            ("tls.cert_path", &self.cert_path),
+            ("tls.key_path", &self.key_path),
+        ] {
+            if path.as_os_str().is_empty() {
+                return Err(ConfigError::Invalid(format!("{name} must not be empty")));
+            }
+        }
+        if self.cert_path == self.key_path {
+            return Err(ConfigError::Invalid(
+                "tls.cert_path and tls.key_path must be different files".to_string(),
+            ));
+        }
+        if self
+            .redirect_bind
+            .is_some_and(|redirect| redirect.port() == bind.port())
+        {
+            return Err(ConfigError::Invalid(
+                "tls.redirect_bind must use another port than server.bind".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `topic` can be published to.
+fn check_topic(name: &str, topic: &str) -> Result<(), ConfigError> {
+    if topic.is_empty() || topic.contains(['+', '#']) {
+        return Err(ConfigError::Invalid(format!(
+            "{name} must be a non-empty topic without wildcards, got {topic:?}"
+        )));
+    }
+    Ok(())
+}
+
+impl NotificationChannel {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| {
+            ConfigError::Invalid(format!("notification channel {:?}: {msg}", self.name))
+        };
+
+        if self.name.is_empty() {
+            return Err(ConfigError::Invalid(
+                "notification channel names must not be empty".to_string(),
+            ));
+        }
+        for (field, template) in [("title", &self.title), ("body", &self.body)] {
+            template::check(template).map_err(|err| invalid(format!("`{field}`: {err}")))?;
+        }
+
+        if self.kind == NotifierKind::Email {
+            if self.smtp_host.as_deref().is_none_or(str::is_empty) {
+                return Err(invalid("`smtp_host` is required".to_string()));
+            }
+            if self.smtp_username.is_some() != self.smtp_password.is_some() {
+                return Err(invalid(
+                    "`smtp_username` and `smtp_password` go together".to_string(),
+                ));
+            }
+            match &self.from {
+                Some(from) => {
+                    check_mailbox(from).map_err(|err| invalid(format!("`from`: {err}")))?
+                }
+                None => return Err(invalid("`from` is required".to_string())),
+            }
+            if self.to.is_empty() {
+                return Err(invalid("`to` must list at least one address".to_string()));
+            }
+            for to in &self.to {
+                check_mailbox(to).map_err(|err| invalid(format!("`to`: {err}")))?;
+            }
+        } else {
+            match &self.url {
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => {}
+                Some(url) => {
+                    return Err(invalid(format!(
+                        "`url` must be an http:// or https:// URL, got {url}"
+                    )))
+                }
+                None => return Err(invalid("`url` is required".to_string())),
+            }
+            if self.kind == NotifierKind::Gotify && self.token.is_none() {
+                return Err(invalid("`token` is required by Gotify".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_mailbox(address: &str) -> Result<(), String> {
+    address
+        .parse::<Mailbox>()
+        .map(|_| ())
+        .map_err(|err| format!("invalid address {address:?}: {err}"))
+}
+
+impl SensorConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| ConfigError::Invalid(format!("sensor {:?}: {msg}", self.name));
+
+        // Names and columns are used as SQL identifiers and metric names.
+        let is_identifier = |name: &str| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !is_identifier(&self.name) {
+            return Err(ConfigError::Invalid(format!(
+                "sensor name {:?} must be made of ASCII letters, digits and `_`",
+                self.name
+            )));
+        }
+        if self.name == "timestamp" {
+            return Err(invalid("`timestamp` is reserved"));
+        }
+        if let Some(column) = &self.column {
+            if !is_identifier(column) {
+                return Err(invalid(
+                    "`column` must be made of ASCII letters, digits and `_`",
+                ));
+            }
+        }
+        if let Some(metric) = &self.metric {
+            if !is_identifier(metric) {
+                return Err(invalid(
+                    "`metric` must be made of ASCII letters, digits and `_`",
+                ));
+            }
+        }
+        for bound in [self.min, self.max].into_iter().flatten() {
+            if !bound.is_finite() {
+                return Err(invalid("`min` and `max` must be numbers"));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(invalid("`min` must not be greater than `max`"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AlertRule {
+    fn validate(&self, sensors: &[SensorConfig]) -> Result<(), ConfigError> {
+        let invalid =
+            |msg: &str| ConfigError::Invalid(format!("alert rule {:?}: {msg}", self.name));
+
+        if self.name.is_empty() {
+            return Err(ConfigError::Invalid(
+                "alert rule names must not be empty".to_string(),
+            ));
+        }
+        if self.kind != AlertKind::NoData {
+            let Some(measurement) = &self.measurement else {
+                return Err(invalid("`measurement` is required"));
+            };
+            if !sensors
+                .iter()
+                .any(|sensor| &sensor.name == measurement && sensor.column.is_some())
+            {
+                return Err(invalid(&format!(
+                    "`measurement` {measurement:?} is not a column of the sensor registry"
+                )));
+            }
+            match self.threshold {
+                None => return Err(invalid("`threshold` is required")),
+                Some(threshold) if !threshold.is_finite() => {
+                    return Err(invalid("`threshold` must be a number"))
+                }
+                Some(threshold) if self.kind == AlertKind::Rate && threshold == 0.0 => {
+                    return Err(invalid("`threshold` must not be 0"))
+                }
+                Some(_) => {}
+            }
+        }
+        if self.window_secs == 0 {
+            return Err(invalid("`window_secs` must be at least 1"));
+        }
+        if !(self.hysteresis >= 0.0 && self.hysteresis.is_finite()) {
+            return Err(invalid("`hysteresis` must be a positive number"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        [server]
+        bind = "127.0.0.1:3000"
+
+        [database]
+        path = "/tmp/sensors.db"
+        dashboard_path = "/tmp/dashboard.db"
+        utc_timestamps = true
+
+        [weather]
+        latitude = 59.91
+        longitude = 10.75
+        providers = ["met-norway", "open-meteo"]
+
+        [[sensors]]
+        name = "temperature"
+        column = "bmp280_temp"
+        unit = "°C"
+        min = -40
+        max = 85
+
+        [[sensors]]
+        name = "co2"
+        unit = "ppm"
+
+        [[devices]]
+        id = "attic"
+        location = "Attic"
+        api_key = "0123456789abcdef"
+    "#;
+
+    fn sample() -> Config {
+        toml::from_str(SAMPLE).unwrap()
+    }
+
+    fn assert_invalid(config: &Config, expected: &str) {
+        match config.validate() {
+            Err(ConfigError::Invalid(msg)) => assert!(msg.contains(expected), "{msg}"),
+            other => panic!("expected an error about {expected:?}, got {other:?}"),
+        }
+    }
+
+    /// Writes `content` to a configuration file of the test `name`.
+    fn write_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pi-home-dashboard-config-{name}-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_a_sample_file() {
+        let config = sample();
+        config.validate().unwrap();
+
+        assert_eq!(config.server.bind, "127.0.0.1:3000".parse().unwrap());
+        assert!(config.database.utc_timestamps);
+        assert_eq!(
+            config.weather.providers,
+            [
+                WeatherProviderKind::MetNorway,
+                WeatherProviderKind::OpenMeteo
+            ]
+        );
+        assert_eq!(config.sensors.len(), 2);
+        assert_eq!(config.sensors[0].column.as_deref(), Some("bmp280_temp"));
+        assert_eq!(config.sensors[1].column, None);
+        assert_eq!(config.devices[0].location.as_deref(), Some("Attic"));
+        // Sections left out keep their defaults.
+        assert_eq!(config.dashboard.refresh_secs, 30);
+        assert!(!config.tls.enabled);
+    }
+
+    #[test]
+    fn the_example_file_is_valid() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+        Config::from_file(&path).unwrap().validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        for content in [
+            "[server]\nbind = \"127.0.0.1:3000\"\nport = 3000\n",
+            "[databse]\npath = \"/tmp/sensors.db\"\n",
+            "[[sensors]]\nname = \"co2\"\ncolumn_name = \"co2\"\n",
+        ] {
+            let err = toml::from_str::<Config>(content).unwrap_err();
+            assert!(err.message().starts_with("unknown field"), "{err}");
+        }
+
+        let path = write_file("unknown-field", "[auth]\nenable = true\n");
+        let err = Config::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, ConfigError::Parse(..)), "{err}");
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_environment_and_the_file() {
+        let path = write_file(
+            "precedence",
+            "[server]\nbind = \"127.0.0.1:1000\"\n[weather]\nlatitude = 10.0\nlongitude = 20.0\n",
+        );
+        let load = |args: &[&str]| {
+            let cli = Cli::try_parse_from(
+                ["pi-home-dashboard", "--config", path.to_str().unwrap()]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap();
+            Config::load(cli).unwrap()
+        };
+
+        // No other test reads these variables.
+        std::env::set_var("PI_HOME_DASHBOARD_BIND", "127.0.0.1:2000");
+        std::env::set_var("PI_HOME_DASHBOARD_LATITUDE", "-30.5");
+        let from_env = load(&[]);
+        let from_flags = load(&["--bind", "127.0.0.1:3000", "--latitude", "-40.5"]);
+        std::env::remove_var("PI_HOME_DASHBOARD_BIND");
+        std::env::remove_var("PI_HOME_DASHBOARD_LATITUDE");
+        let from_file = load(&[]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.server.bind, "127.0.0.1:1000".parse().unwrap());
+        assert_eq!(from_file.weather.latitude, 10.0);
+        assert_eq!(from_env.server.bind, "127.0.0.1:2000".parse().unwrap());
+        assert_eq!(from_env.weather.latitude, -30.5);
+        assert_eq!(from_flags.server.bind, "127.0.0.1:3000".parse().unwrap());
+        assert_eq!(from_flags.weather.latitude, -40.5);
+        // Settings without a flag or variable keep the file's value.
+        assert_eq!(from_flags.weather.longitude, 20.0);
+    }
+
+    #[test]
+    fn rejects_duplicate_sensor_names() {
+        let mut config = sample();
+        let mut duplicate = config.sensors[1].clone();
+        duplicate.column = Some("htu21d_temp".to_string());
+        config.sensors.push(duplicate);
+        assert_invalid(&config, "sensor \"co2\" is defined twice");
+    }
+
+    #[test]
+    fn rejects_columns_that_are_not_identifiers() {
+        for column in ["bmp280 temp", "1st", "temp; DROP TABLE SensorData", ""] {
+            let mut config = sample();
+            config.sensors[0].column = Some(column.to_string());
+            assert_invalid(&config, "`column` must be made of ASCII letters");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_tls_settings() {
+        let mut config = sample();
+        config.tls.enabled = true;
+        config.validate().unwrap();
+
+        config.tls.key_path = config.tls.cert_path.clone();
+        assert_invalid(&config, "must be different files");
+
+        let mut config = sample();
+        config.tls.enabled = true;
+        config.tls.cert_path = PathBuf::new();
+        assert_invalid(&config, "tls.cert_path must not be empty");
+
+        let mut config = sample();
+        config.tls.enabled = true;
+        config.tls.redirect_bind = Some("0.0.0.0:3000".parse().unwrap());
+        assert_invalid(&config, "tls.redirect_bind must use another port");
+
+        // Only checked when serving HTTPS.
+        config.tls.enabled = false;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_auth_settings() {
+        for ttl in [0, MAX_SESSION_TTL_SECS + 1] {
+            let mut config = sample();
+            config.auth.session_ttl_secs = ttl;
+            assert_invalid(&config, "auth.session_ttl_secs must be between 1");
+        }
+
+        let mut config = sample();
+        config.devices[0].api_key = Some("short".to_string());
+        assert_invalid(&config, "`api_key` must be at least 16 characters long");
+
+        let mut config = sample();
+        config.devices.push(DeviceConfig {
+            id: LOCAL_DEVICE.to_string(),
+            name: None,
+            location: None,
+            api_key: Some("fedcba9876543210".to_string()),
+        });
+        assert_invalid(&config, "cannot have an `api_key`");
+    }
+}
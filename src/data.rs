@@ -0,0 +1,867 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    Json,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{DatabaseConfig, SensorConfig},
+    db,
+    error::AppError,
+    state::AppState,
+};
+
+/// Number of rows returned by `/data` when no `limit` is given.
+const DEFAULT_DATA_LIMIT: u32 = 1000;
+/// Upper bound for the `limit` query parameter of `/data`.
+const MAX_DATA_LIMIT: u32 = 10_000;
+
+/// Format of the `timestamp` column, as written by the sensor logger.
+pub const DB_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Row of the `SensorData` table, with the values of the sensor registry.
+#[derive(Serialize, Clone)]
+pub struct SensorData {
+    pub timestamp: String,
+    /// Values by sensor name, `None` when missing or out of the valid range.
+    #[serde(flatten)]
+    pub values: BTreeMap<String, Option<f64>>,
+}
+
+/// Newest row, served by `/data/latest`.
+#[derive(Serialize)]
+pub struct LatestSensorData {
+    #[serde(flatten)]
+    pub reading: SensorData,
+    /// Seconds elapsed since the reading was taken.
+    pub age_seconds: i64,
+}
+
+/// Period covered by `/data/summary`.
+#[derive(Deserialize, Clone, Copy, Default)]
+pub enum SummaryPeriod {
+    #[default]
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+}
+
+#[derive(Deserialize)]
+pub struct SummaryQuery {
+    #[serde(default)]
+    pub period: SummaryPeriod,
+}
+
+/// Statistics of one measurement over a period, with the time of its
+/// extremes.
+#[derive(Serialize, Debug)]
+pub struct MeasurementSummary {
+    pub min: f64,
+    pub min_timestamp: String,
+    pub max: f64,
+    pub max_timestamp: String,
+    pub mean: f64,
+    pub count: u32,
+}
+
+/// Statistics of each measurement since `from`, served by `/data/summary`.
+#[derive(Serialize)]
+pub struct SensorSummary {
+    pub from: String,
+    /// Statistics by sensor name, `None` when there is no value.
+    #[serde(flatten)]
+    pub measurements: BTreeMap<String, Option<MeasurementSummary>>,
+}
+
+/// Query parameters accepted by `/data`.
+///
+/// `from` and `to` bound the time range (both inclusive), `before` is the
+/// pagination cursor: pass the `next_before` value of the previous page to
+/// get the next (older) one. `/data` cursors are written
+/// `<timestamp>,<rowid>`, so that rows sharing a timestamp are not skipped;
+/// a plain timestamp starts with the rows older than it.
+#[derive(Deserialize)]
+pub struct DataQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<u32>,
+    pub before: Option<String>,
+}
+
+/// Validated [`DataQuery`], with timestamps in the database format.
+pub struct PageParams {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub before: Option<String>,
+    /// Rowid of the `before` cursor, among the rows of its timestamp.
+    pub before_rowid: Option<i64>,
+    pub limit: u32,
+}
+
+/// One page of rows, newest first.
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub has_more: bool,
+    pub next_before: Option<String>,
+}
+
+/// Query parameters accepted by `/data/aggregate`.
+///
+/// `bucket` is a duration such as `30s`, `15m`, `1h` or `1d`.
+#[derive(Deserialize)]
+pub struct AggregateQuery {
+    pub bucket: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Statistics of one measurement over a bucket.
+#[derive(Serialize)]
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub count: u32,
+}
+
+/// Sensor data aggregated over one time bucket.
+#[derive(Serialize)]
+pub struct AggregatedSensorData {
+    /// Start of the bucket.
+    pub timestamp: String,
+    /// Statistics by sensor name, `None` when there is no value.
+    #[serde(flatten)]
+    pub measurements: BTreeMap<String, Option<Stats>>,
+}
+
+impl SensorConfig {
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn chart(&self) -> &str {
+        self.chart.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Name of the `/metrics` gauge of the latest value, without the prefix.
+    pub fn metric_name(&self) -> String {
+        match &self.metric {
+            Some(metric) => metric.clone(),
+            None => format!("sensor_{}", self.name),
+        }
+    }
+
+    /// SQL expression of the values of the `SensorData` column, NULL when
+    /// out of the valid range. `None` if the sensor has no column.
+    pub fn value_sql(&self) -> Option<String> {
+        let column = self.column.as_deref()?;
+        let bounds = [
+            self.min.map(|min| format!("{column} >= {min}")),
+            self.max.map(|max| format!("{column} <= {max}")),
+        ];
+        let bounds: Vec<_> = bounds.into_iter().flatten().collect();
+        Some(if bounds.is_empty() {
+            column.to_string()
+        } else {
+            format!("CASE WHEN {} THEN {column} END", bounds.join(" AND "))
+        })
+    }
+
+    /// Whether `value` is within the valid range.
+    pub fn is_valid(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+/// Sensors of the registry read from the `SensorData` table.
+pub fn table_sensors(sensors: &[SensorConfig]) -> impl Iterator<Item = &SensorConfig> {
+    sensors.iter().filter(|sensor| sensor.column.is_some())
+}
+
+/// Selected columns mapped by [`SensorData::from_row`].
+pub fn select_sensor_data(sensors: &[SensorConfig]) -> String {
+    std::iter::once("timestamp".to_string())
+        .chain(table_sensors(sensors).filter_map(SensorConfig::value_sql))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl SensorData {
+    /// Maps a row selecting the columns given by [`select_sensor_data`].
+    pub fn from_row(row: &rusqlite::Row, sensors: &[SensorConfig]) -> rusqlite::Result<Self> {
+        let mut values = BTreeMap::new();
+        for (i, sensor) in table_sensors(sensors).enumerate() {
+            values.insert(sensor.name.clone(), row.get(i + 1)?);
+        }
+        Ok(SensorData {
+            timestamp: row.get(0)?,
+            values,
+        })
+    }
+
+    /// Value of the named sensor, if any.
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.values.get(name).copied().flatten()
+    }
+}
+
+impl SummaryPeriod {
+    fn duration(self) -> TimeDelta {
+        match self {
+            SummaryPeriod::Day => TimeDelta::days(1),
+            SummaryPeriod::Week => TimeDelta::days(7),
+            SummaryPeriod::Month => TimeDelta::days(30),
+        }
+    }
+}
+
+impl DataQuery {
+    pub fn parse(&self) -> Result<PageParams, AppError> {
+        let from = parse_timestamp_param("from", self.from.as_deref())?;
+        let to = parse_timestamp_param("to", self.to.as_deref())?;
+        let (before, before_rowid) = match self.before.as_deref() {
+            Some(before) => parse_cursor(before)?,
+            None => (None, None),
+        };
+        let limit = parse_limit(self.limit)?;
+        check_range(&from, &to)?;
+
+        Ok(PageParams {
+            from,
+            to,
+            before,
+            before_rowid,
+            limit,
+        })
+    }
+}
+
+impl<T> Page<T> {
+    /// Builds a page from up to `limit + 1` rows, the extra one only telling
+    /// that another page exists. `cursor` gives the `before` value of the
+    /// next page from the last row.
+    pub fn from_rows(mut rows: Vec<T>, limit: u32, cursor: impl Fn(&T) -> String) -> Self {
+        let has_more = rows.len() > limit as usize;
+        rows.truncate(limit as usize);
+        let next_before = if has_more {
+            rows.last().map(cursor)
+        } else {
+            None
+        };
+
+        Self {
+            data: rows,
+            has_more,
+            next_before,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            data: self.data.into_iter().map(f).collect(),
+            has_more: self.has_more,
+            next_before: self.next_before,
+        }
+    }
+}
+
+pub async fn get_data(
+    State(state): State<AppState>,
+    query: Result<Query<DataQuery>, QueryRejection>,
+) -> Result<Json<Page<SensorData>>, AppError> {
+    let Query(query) = query?;
+    let params = query.parse()?;
+
+    // One extra row is fetched to know whether another page exists.
+    let limit = params.limit;
+    let config = state.config.clone();
+    let sensors = db::run(&state.db, move |conn| {
+        query_data(
+            conn,
+            &config.sensors,
+            params.from,
+            params.to,
+            params.before,
+            params.before_rowid,
+            limit + 1,
+        )
+    })
+    .await?;
+
+    Ok(Json(
+        Page::from_rows(sensors, limit, cursor).map(|(_, row)| row),
+    ))
+}
+
+/// `before` value of the page following `row`.
+pub fn cursor((rowid, row): &(i64, SensorData)) -> String {
+    format!("{},{rowid}", row.timestamp)
+}
+
+/// Parses a `before` cursor, a timestamp optionally followed by a rowid.
+fn parse_cursor(value: &str) -> Result<(Option<String>, Option<i64>), AppError> {
+    let (timestamp, rowid) = match value.rsplit_once(',') {
+        Some((timestamp, rowid)) => {
+            let rowid = rowid.trim().parse().map_err(|_| {
+                AppError::BadRequest(format!("`before` has an invalid rowid: {value}"))
+            })?;
+            (timestamp, Some(rowid))
+        }
+        None => (value, None),
+    };
+    Ok((parse_timestamp_param("before", Some(timestamp))?, rowid))
+}
+
+pub async fn get_aggregated_data(
+    State(state): State<AppState>,
+    query: Result<Query<AggregateQuery>, QueryRejection>,
+) -> Result<Json<Vec<AggregatedSensorData>>, AppError> {
+    let Query(query) = query?;
+    let bucket_secs = parse_bucket(&query.bucket)?;
+    let from = parse_timestamp_param("from", query.from.as_deref())?;
+    let to = parse_timestamp_param("to", query.to.as_deref())?;
+    check_range(&from, &to)?;
+
+    let config = state.config.clone();
+    let buckets = db::run(&state.db, move |conn| {
+        query_aggregated_data(conn, &config.sensors, bucket_secs, from, to)
+    })
+    .await?;
+
+    Ok(Json(buckets))
+}
+
+pub async fn get_latest_data(
+    State(state): State<AppState>,
+) -> Result<Json<LatestSensorData>, AppError> {
+    let config = state.config.clone();
+    let reading = db::run(&state.db, move |conn| query_latest(conn, &config.sensors))
+        .await?
+        .ok_or_else(|| AppError::NotFound("no sensor data recorded yet".to_string()))?;
+
+    let age_seconds = reading_age(&reading, &state.config.database)?;
+
+    Ok(Json(LatestSensorData {
+        reading,
+        age_seconds,
+    }))
+}
+
+pub async fn get_summary(
+    State(state): State<AppState>,
+    query: Result<Query<SummaryQuery>, QueryRejection>,
+) -> Result<Json<SensorSummary>, AppError> {
+    let Query(query) = query?;
+    let from = (db_now(&state.config.database) - query.period.duration())
+        .format(DB_TIMESTAMP_FORMAT)
+        .to_string();
+
+    let config = state.config.clone();
+    let summary = db::run(&state.db, move |conn| {
+        query_summary(conn, &config.sensors, &from)
+    })
+    .await?;
+    Ok(Json(summary))
+}
+
+/// Current time, in the time zone of the `timestamp` column.
+pub fn db_now(config: &DatabaseConfig) -> NaiveDateTime {
+    if config.utc_timestamps {
+        Utc::now().naive_utc()
+    } else {
+        Local::now().naive_local()
+    }
+}
+
+/// Validates the `limit` query parameter, defaulting to
+/// [`DEFAULT_DATA_LIMIT`].
+pub fn parse_limit(limit: Option<u32>) -> Result<u32, AppError> {
+    match limit {
+        None => Ok(DEFAULT_DATA_LIMIT),
+        Some(limit) if (1..=MAX_DATA_LIMIT).contains(&limit) => Ok(limit),
+        Some(limit) => Err(AppError::BadRequest(format!(
+            "`limit` must be between 1 and {MAX_DATA_LIMIT}, got {limit}"
+        ))),
+    }
+}
+
+/// Returns the number of seconds elapsed since `reading` was recorded.
+pub fn reading_age(reading: &SensorData, config: &DatabaseConfig) -> Result<i64, AppError> {
+    NaiveDateTime::parse_from_str(&reading.timestamp, DB_TIMESTAMP_FORMAT)
+        .map(|timestamp| (db_now(config) - timestamp).num_seconds())
+        .map_err(|err| {
+            AppError::Internal(format!(
+                "invalid timestamp in database {}: {err}",
+                reading.timestamp
+            ))
+        })
+}
+
+/// Returns the registry columns missing from the `SensorData` table, if it
+/// exists.
+pub fn missing_columns(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+) -> rusqlite::Result<Vec<String>> {
+    let columns = conn
+        .prepare("SELECT name FROM pragma_table_info('SensorData')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(table_sensors(sensors)
+        .filter_map(|sensor| sensor.column.clone())
+        .filter(|column| !columns.contains(column))
+        .collect())
+}
+
+/// Returns the newest row, if any.
+pub fn query_latest(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+) -> rusqlite::Result<Option<SensorData>> {
+    Ok(query_data(conn, sensors, None, None, None, None, 1)?
+        .pop()
+        .map(|(_, row)| row))
+}
+
+/// Summarizes each measurement over the rows since `from`.
+fn query_summary(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    from: &str,
+) -> rusqlite::Result<SensorSummary> {
+    let mut measurements = BTreeMap::new();
+    for sensor in table_sensors(sensors) {
+        if let Some(value) = sensor.value_sql() {
+            measurements.insert(
+                sensor.name.clone(),
+                query_measurement_summary(conn, &value, from)?,
+            );
+        }
+    }
+    Ok(SensorSummary {
+        from: from.to_string(),
+        measurements,
+    })
+}
+
+/// Summarizes the `value` expression over the rows since `from`, `None` if
+/// there is no value.
+fn query_measurement_summary(
+    conn: &Connection,
+    value: &str,
+    from: &str,
+) -> rusqlite::Result<Option<MeasurementSummary>> {
+    let (mean, count): (Option<f64>, u32) = conn
+        .prepare_cached(&format!(
+            "SELECT AVG({value}), COUNT({value}) FROM SensorData WHERE timestamp >= ?1"
+        ))?
+        .query_row([from], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let Some(mean) = mean else {
+        return Ok(None);
+    };
+
+    // With a single MIN() or MAX() aggregate, SQLite takes the other
+    // selected columns from the row holding the extreme value.
+    let extreme = |aggregate: &str| -> rusqlite::Result<(f64, String)> {
+        conn.prepare_cached(&format!(
+            "SELECT {aggregate}({value}), timestamp FROM SensorData WHERE timestamp >= ?1"
+        ))?
+        .query_row([from], |row| Ok((row.get(0)?, row.get(1)?)))
+    };
+    let (min, min_timestamp) = extreme("MIN")?;
+    let (max, max_timestamp) = extreme("MAX")?;
+
+    Ok(Some(MeasurementSummary {
+        min,
+        min_timestamp,
+        max,
+        max_timestamp,
+        mean,
+        count,
+    }))
+}
+
+/// Returns up to `limit` rows in the given time range with their rowid,
+/// newest first.
+///
+/// Rows are ordered by timestamp then rowid, so that the `before` cursor
+/// tells apart the rows of the same timestamp. Without `before_rowid`, the
+/// comparison is NULL for the rows of the `before` timestamp, which are
+/// excluded.
+pub fn query_data(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    from: Option<String>,
+    to: Option<String>,
+    before: Option<String>,
+    before_rowid: Option<i64>,
+    limit: u32,
+) -> rusqlite::Result<Vec<(i64, SensorData)>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}, rowid \
+         FROM SensorData \
+         WHERE (?1 IS NULL OR timestamp >= ?1) \
+           AND (?2 IS NULL OR timestamp <= ?2) \
+           AND (?3 IS NULL OR (timestamp, rowid) < (?3, ?4)) \
+         ORDER BY timestamp DESC, rowid DESC \
+         LIMIT ?5",
+        select_sensor_data(sensors)
+    ))?;
+
+    let rowid_column = table_sensors(sensors).count() + 1;
+    let rows = stmt.query_map(params![from, to, before, before_rowid, limit], |row| {
+        Ok((row.get(rowid_column)?, SensorData::from_row(row, sensors)?))
+    })?;
+    rows.collect()
+}
+
+/// Returns up to `limit` rows with their rowid following the
+/// `(timestamp, rowid)` of `after` (or the newest row when `after` is
+/// `None`), oldest first.
+///
+/// Comparing the rowid too keeps the rows inserted later with the timestamp
+/// of `after`.
+pub fn query_data_after(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    after: Option<(&str, i64)>,
+    limit: u32,
+) -> rusqlite::Result<Vec<(i64, SensorData)>> {
+    let Some((after, after_rowid)) = after else {
+        return query_data(conn, sensors, None, None, None, None, 1);
+    };
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}, rowid \
+         FROM SensorData \
+         WHERE (timestamp, rowid) > (?1, ?2) \
+         ORDER BY timestamp ASC, rowid ASC \
+         LIMIT ?3",
+        select_sensor_data(sensors)
+    ))?;
+    let rowid_column = table_sensors(sensors).count() + 1;
+    let rows = stmt.query_map(params![after, after_rowid, limit], |row| {
+        Ok((row.get(rowid_column)?, SensorData::from_row(row, sensors)?))
+    })?;
+    rows.collect()
+}
+
+/// Aggregates the rows in the given time range into buckets of
+/// `bucket_secs` seconds, oldest first.
+fn query_aggregated_data(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    bucket_secs: i64,
+    from: Option<String>,
+    to: Option<String>,
+) -> rusqlite::Result<Vec<AggregatedSensorData>> {
+    let sensors: Vec<_> = table_sensors(sensors)
+        .filter_map(|sensor| Some((&sensor.name, sensor.value_sql()?)))
+        .collect();
+    let aggregates: String = sensors
+        .iter()
+        .map(|(_, value)| format!(", MIN({value}), MAX({value}), AVG({value}), COUNT({value})"))
+        .collect();
+
+    // Rows are grouped by the start of their bucket, computed from the Unix
+    // time so that buckets are aligned on multiples of the bucket size.
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT datetime((CAST(strftime('%s', timestamp) AS INTEGER) / ?1) * ?1, 'unixepoch') AS bucket{aggregates} \
+         FROM SensorData \
+         WHERE (?2 IS NULL OR timestamp >= ?2) \
+           AND (?3 IS NULL OR timestamp <= ?3) \
+         GROUP BY bucket \
+         HAVING bucket IS NOT NULL \
+         ORDER BY bucket ASC"
+    ))?;
+
+    let stats = |row: &rusqlite::Row, first: usize| -> rusqlite::Result<Option<Stats>> {
+        let count = row.get(first + 3)?;
+        if count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Stats {
+            min: row.get(first)?,
+            max: row.get(first + 1)?,
+            mean: row.get(first + 2)?,
+            count,
+        }))
+    };
+
+    let rows = stmt.query_map(params![bucket_secs, from, to], |row| {
+        let mut measurements = BTreeMap::new();
+        for (i, (name, _)) in sensors.iter().enumerate() {
+            measurements.insert(name.to_string(), stats(row, 1 + 4 * i)?);
+        }
+        Ok(AggregatedSensorData {
+            timestamp: row.get(0)?,
+            measurements,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn check_range(from: &Option<String>, to: &Option<String>) -> Result<(), AppError> {
+    match (from, to) {
+        (Some(from), Some(to)) if from > to => Err(AppError::BadRequest(
+            "`from` must not be later than `to`".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a bucket duration such as `30s`, `15m`, `1h` or `1d` into seconds.
+fn parse_bucket(value: &str) -> Result<i64, AppError> {
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "`bucket` must be a positive duration such as 30s, 15m, 1h or 1d, got {value}"
+        ))
+    };
+
+    let value = value.trim();
+    let Some(unit) = value.chars().last() else {
+        return Err(invalid());
+    };
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    match amount.checked_mul(unit_secs) {
+        Some(secs) if secs > 0 => Ok(secs),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses an optional timestamp query parameter and normalizes it to the
+/// format stored in the database, so that it can be compared as text.
+pub fn parse_timestamp_param(name: &str, value: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    match normalize_timestamp(value) {
+        Some(timestamp) => Ok(Some(timestamp)),
+        None => Err(AppError::BadRequest(format!(
+            "`{name}` is not a valid timestamp (expected e.g. 2026-10-01T00:00): {value}"
+        ))),
+    }
+}
+
+/// Converts a timestamp to the format stored in the database.
+///
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM` and `YYYY-MM-DDTHH:MM:SS`
+/// (a space may be used instead of the `T`).
+pub fn normalize_timestamp(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    let parsed = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    });
+
+    parsed.map(|timestamp| timestamp.format(DB_TIMESTAMP_FORMAT).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn sensors() -> Vec<SensorConfig> {
+        Config::default().sensors
+    }
+
+    /// In-memory database with the schema of the sensor logger.
+    fn test_db(rows: &[(&str, f32, f32, f32, f32)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE SensorData (
+                 timestamp TEXT,
+                 bmp280_temperature REAL,
+                 bmp280_pressure REAL,
+                 htu21d_temperature REAL,
+                 htu21d_humidity REAL
+             );",
+        )
+        .unwrap();
+        for row in rows {
+            conn.execute(
+                "INSERT INTO SensorData VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![row.0, row.1, row.2, row.3, row.4],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn latest_is_newest_row() {
+        let conn = test_db(&[
+            ("2026-10-15 10:00:00", 20.0, 1010.0, 20.5, 55.0),
+            ("2026-10-16 09:00:00", 21.0, 1012.0, 21.5, 60.0),
+            ("2026-10-14 08:00:00", 19.0, 1008.0, 19.5, 50.0),
+        ]);
+
+        let latest = query_latest(&conn, &sensors()).unwrap().unwrap();
+
+        assert_eq!(latest.timestamp, "2026-10-16 09:00:00");
+        assert_eq!(latest.value("htu21d_humidity"), Some(60.0));
+    }
+
+    #[test]
+    fn latest_of_empty_table_is_none() {
+        let conn = test_db(&[]);
+
+        assert!(query_latest(&conn, &sensors()).unwrap().is_none());
+    }
+
+    #[test]
+    fn summary_reports_extremes_and_their_time() {
+        let conn = test_db(&[
+            ("2026-10-01 12:00:00", -5.0, 990.0, -4.0, 99.0),
+            ("2026-10-15 06:00:00", 10.0, 1000.0, 11.0, 70.0),
+            ("2026-10-15 14:00:00", 24.0, 1020.0, 25.0, 40.0),
+            ("2026-10-16 06:00:00", 8.0, 1010.0, 9.0, 80.0),
+        ]);
+
+        let summary = query_summary(&conn, &sensors(), "2026-10-15 00:00:00").unwrap();
+
+        // The row from 2026-10-01 is out of the period.
+        let temp = summary.measurements["bmp280_temp"].as_ref().unwrap();
+        assert_eq!(temp.min, 8.0);
+        assert_eq!(temp.min_timestamp, "2026-10-16 06:00:00");
+        assert_eq!(temp.max, 24.0);
+        assert_eq!(temp.max_timestamp, "2026-10-15 14:00:00");
+        assert_eq!(temp.mean, 14.0);
+        assert_eq!(temp.count, 3);
+
+        let humidity = summary.measurements["htu21d_humidity"].as_ref().unwrap();
+        assert_eq!(humidity.max, 80.0);
+        assert_eq!(humidity.max_timestamp, "2026-10-16 06:00:00");
+    }
+
+    #[test]
+    fn summary_of_empty_period_has_no_values() {
+        let conn = test_db(&[("2026-10-01 12:00:00", 20.0, 1000.0, 20.0, 50.0)]);
+
+        let summary = query_summary(&conn, &sensors(), "2026-10-15 00:00:00").unwrap();
+
+        assert!(summary.measurements["bmp280_temp"].is_none());
+        assert!(summary.measurements["htu21d_humidity"].is_none());
+    }
+
+    #[test]
+    fn values_out_of_range_are_ignored() {
+        let conn = test_db(&[
+            ("2026-10-16 08:00:00", 20.0, 1010.0, 20.5, 55.0),
+            // Glitch of the HTU21D.
+            ("2026-10-16 09:00:00", 21.0, 1012.0, 21.5, 118.0),
+        ]);
+        let sensors = sensors();
+
+        let latest = query_latest(&conn, &sensors).unwrap().unwrap();
+        assert_eq!(latest.value("htu21d_humidity"), None);
+        assert_eq!(latest.value("bmp280_temp"), Some(21.0));
+
+        let summary = query_summary(&conn, &sensors, "2026-10-16 00:00:00").unwrap();
+        let humidity = summary.measurements["htu21d_humidity"].as_ref().unwrap();
+        assert_eq!((humidity.max, humidity.count), (55.0, 1));
+
+        let buckets = query_aggregated_data(&conn, &sensors, 86400, None, None).unwrap();
+        let humidity = buckets[0].measurements["htu21d_humidity"].as_ref().unwrap();
+        assert_eq!((humidity.max, humidity.count), (55.0, 1));
+    }
+
+    #[test]
+    fn pages_keep_rows_sharing_a_timestamp() {
+        let conn = test_db(&[
+            ("2026-10-16 10:00:00", 20.0, 1010.0, 20.5, 50.0),
+            ("2026-10-16 10:00:01", 20.1, 1010.0, 20.5, 51.0),
+            ("2026-10-16 10:00:01", 20.2, 1010.0, 20.5, 52.0),
+            ("2026-10-16 10:00:01", 20.3, 1010.0, 20.5, 53.0),
+            ("2026-10-16 10:00:02", 20.4, 1010.0, 20.5, 54.0),
+        ]);
+        let sensors = sensors();
+
+        let mut humidities = Vec::new();
+        let mut before: Option<String> = None;
+        loop {
+            let (timestamp, rowid) = match &before {
+                Some(before) => parse_cursor(before).unwrap(),
+                None => (None, None),
+            };
+            let rows = query_data(&conn, &sensors, None, None, timestamp, rowid, 3).unwrap();
+            let page = Page::from_rows(rows, 2, cursor);
+            humidities.extend(
+                page.data
+                    .iter()
+                    .map(|(_, row)| row.value("htu21d_humidity")),
+            );
+            if !page.has_more {
+                break;
+            }
+            before = page.next_before;
+        }
+        assert_eq!(
+            humidities,
+            [54.0, 53.0, 52.0, 51.0, 50.0].map(Some).to_vec()
+        );
+
+        // A plain timestamp returns the rows older than it.
+        let (timestamp, rowid) = parse_cursor("2026-10-16T10:00:01").unwrap();
+        let rows = query_data(&conn, &sensors, None, None, timestamp, rowid, 10).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(cursor(&rows[0]), "2026-10-16 10:00:00,1");
+        assert!(parse_cursor("2026-10-16 10:00:01,last").is_err());
+    }
+
+    #[test]
+    fn registry_columns_are_selected() {
+        let conn = test_db(&[("2026-10-16 08:00:00", 20.0, 1010.0, 20.5, 55.0)]);
+        conn.execute_batch("ALTER TABLE SensorData ADD COLUMN scd30_co2 REAL; UPDATE SensorData SET scd30_co2 = 612;")
+            .unwrap();
+        // The default `co2` entry describes the readings of other devices,
+        // giving it a column reads it from `SensorData` too.
+        let mut config = Config::default();
+        let co2 = config
+            .sensors
+            .iter_mut()
+            .find(|sensor| sensor.name == "co2")
+            .unwrap();
+        co2.column = Some("scd30_co2".to_string());
+        config.validate().unwrap();
+        let sensors = config.sensors;
+
+        let latest = query_latest(&conn, &sensors).unwrap().unwrap();
+
+        assert_eq!(latest.value("co2"), Some(612.0));
+        assert_eq!(latest.value("bmp280_pressure"), Some(1010.0));
+    }
+}
@@ -0,0 +1,317 @@
+use std::{fmt, path::Path, time::Duration};
+
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::config::DatabaseConfig;
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+
+/// How long a query waits for the sensor logger to release a write lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a request waits for a free connection in the pool.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum DbError {
+    /// No connection could be taken from the pool in time.
+    Pool(r2d2::Error),
+    Sqlite(rusqlite::Error),
+    /// The blocking task running the query panicked or was cancelled.
+    Task(tokio::task::JoinError),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Pool(err) => write!(f, "database unavailable: {err}"),
+            DbError::Sqlite(err) => write!(f, "database error: {err}"),
+            DbError::Task(err) => write!(f, "database task failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl DbError {
+    /// Whether the error is transient: the pool is exhausted or the
+    /// database is locked by the writer.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            DbError::Pool(_) => true,
+            DbError::Sqlite(err) => matches!(
+                err.sqlite_error_code(),
+                Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+            ),
+            DbError::Task(_) => false,
+        }
+    }
+}
+
+impl From<r2d2::Error> for DbError {
+    fn from(err: r2d2::Error) -> Self {
+        DbError::Pool(err)
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(err: rusqlite::Error) -> Self {
+        DbError::Sqlite(err)
+    }
+}
+
+/// Opens a pool of read-only connections to the dashboard database, with
+/// the sensor database attached.
+///
+/// The sensor database is written by a separate process (the sensor logger)
+/// in whatever journal mode it chose, so connections never take a write lock
+/// on it and the busy timeout covers the moments the logger holds one. The
+/// dashboard database must have been created by [`open_writer`] first.
+pub fn open_pool(config: &DatabaseConfig) -> Result<DbPool, r2d2::Error> {
+    let logger_path = config.path.clone();
+    let manager = SqliteConnectionManager::file(&config.dashboard_path)
+        .with_flags(
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )
+        .with_init(move |conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            attach_logger(conn, &logger_path)?;
+            conn.pragma_update(None, "query_only", true)
+        });
+
+    r2d2::Pool::builder()
+        .max_size(config.pool_size)
+        .connection_timeout(CONNECTION_TIMEOUT)
+        .build(manager)
+}
+
+/// Opens the single read-write connection used for the tables owned by the
+/// dashboard, creating their database if needed. It uses the WAL journal so
+/// that the read pool isn't blocked by its writes. The sensor database is
+/// attached read-only: the dashboard never writes to the logger's file.
+pub fn open_writer(config: &DatabaseConfig) -> Result<DbPool, r2d2::Error> {
+    let logger_path = config.path.clone();
+    let manager = SqliteConnectionManager::file(&config.dashboard_path)
+        .with_flags(
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+                | OpenFlags::SQLITE_OPEN_CREATE
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )
+        .with_init(move |conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.pragma_update_and_check(Some("main"), "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+            attach_logger(conn, &logger_path)
+        });
+
+    r2d2::Pool::builder()
+        .max_size(1)
+        .connection_timeout(CONNECTION_TIMEOUT)
+        .build(manager)
+}
+
+/// Attaches the sensor database read-only as `logger`. Unqualified names
+/// are looked up in the dashboard database first, so queries simply refer
+/// to `SensorData`.
+fn attach_logger(conn: &Connection, path: &Path) -> rusqlite::Result<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS logger",
+        [file_uri(path) + "?mode=ro"],
+    )?;
+    Ok(())
+}
+
+/// `file:` URI of a path, escaping the characters with a meaning in URIs.
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from(if path.is_absolute() {
+        "file://"
+    } else {
+        "file:"
+    });
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            c => uri.push(c),
+        }
+    }
+    uri
+}
+
+/// Creates the tables owned by the dashboard, if they don't exist yet.
+pub fn create_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS ExternalWeather (
+             timestamp TEXT PRIMARY KEY,
+             temperature REAL NOT NULL,
+             windspeed REAL NOT NULL,
+             winddirection REAL NOT NULL,
+             humidity REAL,
+             pressure REAL,
+             weather_code INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS Alerts (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             rule TEXT NOT NULL,
+             message TEXT NOT NULL,
+             value REAL NOT NULL,
+             triggered_at TEXT NOT NULL,
+             resolved_at TEXT,
+             acknowledged_at TEXT
+         );
+         CREATE INDEX IF NOT EXISTS Alerts_rule ON Alerts (rule, triggered_at);
+         CREATE TABLE IF NOT EXISTS NotificationLog (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             alert_id INTEGER NOT NULL REFERENCES Alerts (id),
+             event TEXT NOT NULL,
+             channel TEXT NOT NULL,
+             attempts INTEGER NOT NULL,
+             delivered INTEGER NOT NULL,
+             error TEXT,
+             timestamp TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS devices (
+             id TEXT PRIMARY KEY,
+             name TEXT NOT NULL,
+             location TEXT
+         );
+         CREATE TABLE IF NOT EXISTS sensors (
+             id INTEGER PRIMARY KEY,
+             device_id TEXT NOT NULL REFERENCES devices (id),
+             name TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             unit TEXT NOT NULL,
+             location TEXT,
+             UNIQUE (device_id, name)
+         );
+         CREATE TABLE IF NOT EXISTS readings (
+             sensor_id INTEGER NOT NULL REFERENCES sensors (id),
+             ts TEXT NOT NULL,
+             value REAL NOT NULL,
+             PRIMARY KEY (sensor_id, ts)
+         ) WITHOUT ROWID;
+         CREATE TABLE IF NOT EXISTS users (
+             id INTEGER PRIMARY KEY,
+             username TEXT NOT NULL UNIQUE,
+             password_hash TEXT NOT NULL,
+             role TEXT NOT NULL,
+             created_at TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS sessions (
+             token_hash TEXT PRIMARY KEY,
+             user_id INTEGER NOT NULL REFERENCES users (id),
+             expires_at TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS api_tokens (
+             id INTEGER PRIMARY KEY,
+             user_id INTEGER NOT NULL REFERENCES users (id),
+             name TEXT NOT NULL,
+             token_hash TEXT NOT NULL UNIQUE,
+             created_at TEXT NOT NULL,
+             UNIQUE (user_id, name)
+         );",
+    )?;
+
+    // Columns added after the first release of the table.
+    add_column_if_missing(conn, "ExternalWeather", "humidity", "REAL")?;
+    add_column_if_missing(conn, "ExternalWeather", "pressure", "REAL")?;
+    add_column_if_missing(conn, "sensors", "label", "TEXT")?;
+    Ok(())
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition};"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Runs `query` on a pooled connection, on tokio's blocking thread pool so
+/// that slow queries don't stall the async executor.
+pub async fn run<T, F>(pool: &DbPool, query: F) -> Result<T, DbError>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get()?;
+        Ok(query(&conn)?)
+    })
+    .await
+    .map_err(DbError::Task)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashboard_tables_are_kept_out_of_the_logger_database() {
+        let dir =
+            std::env::temp_dir().join(format!("pi-home-dashboard-db #{}?", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = DatabaseConfig {
+            path: dir.join("data.db"),
+            dashboard_path: dir.join("dashboard.db"),
+            ..DatabaseConfig::default()
+        };
+        Connection::open(&config.path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE SensorData (timestamp TEXT, co2 REAL);
+                 INSERT INTO SensorData VALUES ('2026-10-16 10:00:00', 640);",
+            )
+            .unwrap();
+
+        let writer = open_writer(&config).unwrap();
+        let conn = writer.get().unwrap();
+        create_schema(&conn).unwrap();
+        let mode: String = conn
+            .pragma_query_value(Some("main"), "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let tables: i64 = conn
+            .query_row("SELECT COUNT(*) FROM logger.sqlite_master", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(tables, 1);
+        assert!(conn
+            .execute(
+                "INSERT INTO SensorData VALUES ('2026-10-16 10:01:00', 650)",
+                []
+            )
+            .is_err());
+
+        let pool = open_pool(&config).unwrap();
+        let conn = pool.get().unwrap();
+        let co2: f64 = conn
+            .query_row("SELECT co2 FROM SensorData", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(co2, 640.0);
+        let users: i64 = conn
+            .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(users, 0);
+        drop((conn, pool, writer));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
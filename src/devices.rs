@@ -0,0 +1,462 @@
+use axum::{
+    extract::{rejection::PathRejection, rejection::QueryRejection, Path, Query, State},
+    Json,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::{
+    config::{Config, SensorConfig},
+    data::{
+        self, query_data, query_latest, table_sensors, DataQuery, Page, PageParams, SensorData,
+    },
+    db,
+    error::AppError,
+    state::AppState,
+};
+
+/// Id of the device the sensors of the `SensorData` table are attached to.
+/// Their values are read from that table, the `readings` table holding the
+/// ones of the other devices.
+pub const LOCAL_DEVICE: &str = "local";
+
+/// Device with its sensors, as listed by `/devices`.
+#[derive(Debug, Serialize)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub location: Option<String>,
+    pub sensors: Vec<Sensor>,
+}
+
+/// Sensor with its latest reading, if any.
+#[derive(Debug, Serialize)]
+pub struct Sensor {
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    pub unit: String,
+    pub label: String,
+    pub location: Option<String>,
+    pub last_timestamp: Option<String>,
+    pub last_value: Option<f64>,
+}
+
+/// Values of the sensors of a device measured at the same time, keyed by
+/// sensor name.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DeviceData {
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub values: serde_json::Map<String, serde_json::Value>,
+}
+
+pub async fn get_devices(State(state): State<AppState>) -> Result<Json<Vec<Device>>, AppError> {
+    let config = state.config.clone();
+    let devices = db::run(&state.db, move |conn| query_devices(conn, &config.sensors)).await?;
+    Ok(Json(devices))
+}
+
+pub async fn get_device_data(
+    State(state): State<AppState>,
+    id: Result<Path<String>, PathRejection>,
+    query: Result<Query<DataQuery>, QueryRejection>,
+) -> Result<Json<Page<DeviceData>>, AppError> {
+    let Path(id) = id?;
+    let Query(query) = query?;
+    let params = query.parse()?;
+
+    let device = id.clone();
+    let config = state.config.clone();
+    let page = db::run(&state.db, move |conn| {
+        if !device_exists(conn, &device)? {
+            return Ok(None);
+        }
+        query_device_page(conn, &config.sensors, &device, params).map(Some)
+    })
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("no device {id:?}")))?;
+
+    Ok(Json(page))
+}
+
+/// Returns a page of the values of a device, read from `SensorData` for the
+/// local one.
+fn query_device_page(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    device: &str,
+    params: PageParams,
+) -> rusqlite::Result<Page<DeviceData>> {
+    // One extra row is fetched to know whether another page exists.
+    let limit = params.limit;
+    if device == LOCAL_DEVICE {
+        let rows = query_data(
+            conn,
+            sensors,
+            params.from,
+            params.to,
+            params.before,
+            params.before_rowid,
+            limit + 1,
+        )?;
+        return Ok(Page::from_rows(rows, limit, data::cursor).map(|(_, row)| row.into()));
+    }
+
+    let rows = query_device_data(
+        conn,
+        device,
+        params.from,
+        params.to,
+        params.before,
+        limit + 1,
+    )?;
+    Ok(Page::from_rows(rows, limit, |row| row.timestamp.clone()))
+}
+
+impl From<SensorData> for DeviceData {
+    /// Keeps the values of the row, skipping the missing ones.
+    fn from(row: SensorData) -> Self {
+        DeviceData {
+            timestamp: row.timestamp,
+            values: row
+                .values
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value?.into())))
+                .collect(),
+        }
+    }
+}
+
+/// Gives the configured names and locations to the devices, creating the
+/// ones that haven't reported yet, and the kind, unit and label of the
+/// sensor registry to the sensors.
+///
+/// The local device gets a sensor per registry column when the `SensorData`
+/// table exists, and loses those of the columns removed from the registry.
+pub fn apply_config(conn: &Connection, config: &Config) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    if table_exists(&tx, "SensorData")? {
+        let columns: Vec<&str> = table_sensors(&config.sensors)
+            .map(|sensor| sensor.name.as_str())
+            .collect();
+        for name in &columns {
+            ensure_sensor(&tx, &config.sensors, LOCAL_DEVICE, name)?;
+        }
+        let mut stmt = tx.prepare("SELECT name FROM sensors WHERE device_id = ?1")?;
+        let removed = stmt
+            .query_map([LOCAL_DEVICE], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|name| !columns.contains(&name.as_str()));
+        for name in removed {
+            tx.execute(
+                "DELETE FROM sensors WHERE device_id = ?1 AND name = ?2",
+                params![LOCAL_DEVICE, name],
+            )?;
+        }
+    }
+    for device in &config.devices {
+        let name = device.name.as_deref().unwrap_or(&device.id);
+        tx.execute(
+            "INSERT INTO devices (id, name, location) VALUES (?1, ?2, ?3) \
+             ON CONFLICT (id) DO UPDATE SET name = ?2, location = ?3",
+            params![device.id, name, device.location],
+        )?;
+        tx.execute(
+            "UPDATE sensors SET location = ?2 WHERE device_id = ?1",
+            params![device.id, device.location],
+        )?;
+    }
+    for sensor in &config.sensors {
+        tx.execute(
+            "UPDATE sensors SET kind = ?2, unit = ?3, label = ?4 WHERE name = ?1",
+            params![sensor.name, sensor.chart(), sensor.unit, sensor.label()],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Returns the id of a sensor, creating it and its device on first use.
+///
+/// New sensors get the location of their device, and the kind, unit and
+/// label of the registry entry of the same name, or a kind and unit guessed
+/// from their name.
+pub fn ensure_sensor(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    device: &str,
+    name: &str,
+) -> rusqlite::Result<i64> {
+    if let Some(id) = conn
+        .prepare_cached("SELECT id FROM sensors WHERE device_id = ?1 AND name = ?2")?
+        .query_row(params![device, name], |row| row.get(0))
+        .optional()?
+    {
+        return Ok(id);
+    }
+
+    let (kind, unit, label) = match sensors.iter().find(|sensor| sensor.name == name) {
+        Some(sensor) => (sensor.chart(), sensor.unit.as_str(), sensor.label()),
+        None => {
+            let (kind, unit) = guess_kind(name);
+            (kind, unit, name)
+        }
+    };
+    conn.prepare_cached("INSERT OR IGNORE INTO devices (id, name) VALUES (?1, ?1)")?
+        .execute([device])?;
+    conn.prepare_cached(
+        "INSERT INTO sensors (device_id, name, kind, unit, label, location) \
+         VALUES (?1, ?2, ?3, ?4, ?5, (SELECT location FROM devices WHERE id = ?1))",
+    )?
+    .execute(params![device, name, kind, unit, label])?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Guesses the kind and unit of a measurement from its name, e.g.
+/// `temperature` or `outdoor_humidity`.
+fn guess_kind(name: &str) -> (&str, &str) {
+    let name_lower = name.to_ascii_lowercase();
+    if name_lower.contains("temp") {
+        ("temperature", "°C")
+    } else if name_lower.contains("humid") {
+        ("humidity", "%")
+    } else if name_lower.contains("press") {
+        ("pressure", "hPa")
+    } else if name_lower.contains("co2") {
+        ("co2", "ppm")
+    } else {
+        (name, "")
+    }
+}
+
+/// Whether the table exists, in any of the attached databases.
+fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.prepare_cached("SELECT 1 FROM pragma_table_list WHERE type = 'table' AND name = ?1")?
+        .exists([table])
+}
+
+fn device_exists(conn: &Connection, device: &str) -> rusqlite::Result<bool> {
+    conn.prepare_cached("SELECT 1 FROM devices WHERE id = ?1")?
+        .exists([device])
+}
+
+fn query_devices(
+    conn: &Connection,
+    config_sensors: &[SensorConfig],
+) -> rusqlite::Result<Vec<Device>> {
+    let mut devices = conn
+        .prepare_cached("SELECT id, name, location FROM devices ORDER BY location, name")?
+        .query_map([], |row| {
+            Ok(Device {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                location: row.get(2)?,
+                sensors: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut sensors = conn.prepare_cached(
+        "SELECT id, name, kind, unit, COALESCE(label, name), location, \
+                (SELECT ts FROM readings WHERE sensor_id = sensors.id ORDER BY ts DESC LIMIT 1), \
+                (SELECT value FROM readings WHERE sensor_id = sensors.id ORDER BY ts DESC LIMIT 1) \
+         FROM sensors WHERE device_id = ?1 ORDER BY name",
+    )?;
+    for device in &mut devices {
+        let latest = if device.id == LOCAL_DEVICE {
+            query_latest(conn, config_sensors)?
+        } else {
+            None
+        };
+        device.sensors = sensors
+            .query_map([&device.id], |row| {
+                Ok(Sensor {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    kind: row.get(2)?,
+                    unit: row.get(3)?,
+                    label: row.get(4)?,
+                    location: row.get(5)?,
+                    last_timestamp: row.get(6)?,
+                    last_value: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        // The last values of the local device are those of the newest row.
+        if let Some(latest) = latest {
+            for sensor in &mut device.sensors {
+                sensor.last_timestamp = Some(latest.timestamp.clone());
+                sensor.last_value = latest.values.get(&sensor.name).copied().flatten();
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// Returns the values of the sensors of a device at up to `limit`
+/// timestamps, newest first.
+fn query_device_data(
+    conn: &Connection,
+    device: &str,
+    from: Option<String>,
+    to: Option<String>,
+    before: Option<String>,
+    limit: u32,
+) -> rusqlite::Result<Vec<DeviceData>> {
+    let timestamps = conn
+        .prepare_cached(
+            "SELECT DISTINCT readings.ts FROM readings \
+             JOIN sensors ON sensors.id = readings.sensor_id \
+             WHERE sensors.device_id = ?1 \
+               AND (?2 IS NULL OR readings.ts >= ?2) \
+               AND (?3 IS NULL OR readings.ts <= ?3) \
+               AND (?4 IS NULL OR readings.ts < ?4) \
+             ORDER BY readings.ts DESC \
+             LIMIT ?5",
+        )?
+        .query_map(params![device, from, to, before, limit], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    let (Some(newest), Some(oldest)) = (timestamps.first(), timestamps.last()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut rows: Vec<DeviceData> = Vec::with_capacity(timestamps.len());
+    let mut stmt = conn.prepare_cached(
+        "SELECT readings.ts, sensors.name, readings.value FROM readings \
+         JOIN sensors ON sensors.id = readings.sensor_id \
+         WHERE sensors.device_id = ?1 AND readings.ts >= ?2 AND readings.ts <= ?3 \
+         ORDER BY readings.ts DESC, sensors.name",
+    )?;
+    let mut values = stmt.query(params![device, oldest, newest])?;
+    while let Some(value) = values.next()? {
+        let timestamp: String = value.get(0)?;
+        let name: String = value.get(1)?;
+        let value: f64 = value.get(2)?;
+        if rows.last().is_none_or(|row| row.timestamp != timestamp) {
+            rows.push(DeviceData {
+                timestamp,
+                values: serde_json::Map::new(),
+            });
+        }
+        if let Some(row) = rows.last_mut() {
+            row.values.insert(name, value.into());
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeviceConfig;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE SensorData (
+                 timestamp TEXT,
+                 bmp280_temperature REAL,
+                 bmp280_pressure REAL,
+                 htu21d_temperature REAL,
+                 htu21d_humidity REAL
+             );",
+        )
+        .unwrap();
+        db::create_schema(&conn).unwrap();
+        conn
+    }
+
+    fn insert_sensor_data(conn: &Connection, timestamp: &str, temp: f64) {
+        conn.execute(
+            "INSERT INTO SensorData VALUES (?1, ?2, 1013.2, ?2, NULL)",
+            params![timestamp, temp],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn local_device_is_read_from_sensor_data() {
+        let conn = open();
+        let config = Config::default();
+        insert_sensor_data(&conn, "2026-10-16 10:00:00", 20.0);
+        insert_sensor_data(&conn, "2026-10-16 10:01:00", 20.5);
+        insert_sensor_data(&conn, "2026-10-16 10:01:00", 21.0);
+        apply_config(&conn, &config).unwrap();
+
+        let devices = query_devices(&conn, &config.sensors).unwrap();
+        let local = devices
+            .iter()
+            .find(|device| device.id == LOCAL_DEVICE)
+            .unwrap();
+        let temp = local
+            .sensors
+            .iter()
+            .find(|sensor| sensor.name == "bmp280_temp")
+            .unwrap();
+        assert_eq!(temp.last_timestamp.as_deref(), Some("2026-10-16 10:01:00"));
+        assert_eq!(temp.last_value, Some(21.0));
+        // Nothing is copied out of SensorData.
+        let copied: i64 = conn
+            .query_row("SELECT COUNT(*) FROM readings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(copied, 0);
+
+        let params = DataQuery {
+            from: None,
+            to: None,
+            limit: Some(2),
+            before: None,
+        }
+        .parse()
+        .unwrap();
+        let page = query_device_page(&conn, &config.sensors, LOCAL_DEVICE, params).unwrap();
+        let rows = &page.data;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].values["bmp280_temp"], 21.0);
+        assert_eq!(rows[0].values["bmp280_pressure"], 1013.2);
+        // NULL values are skipped.
+        assert!(!rows[0].values.contains_key("htu21d_humidity"));
+
+        let params = DataQuery {
+            from: None,
+            to: None,
+            limit: Some(2),
+            before: page.next_before,
+        }
+        .parse()
+        .unwrap();
+        let page = query_device_page(&conn, &config.sensors, LOCAL_DEVICE, params).unwrap();
+        assert_eq!(page.data.len(), 1);
+        assert_eq!(page.data[0].values["bmp280_temp"], 20.0);
+        assert_eq!(page.next_before, None);
+    }
+
+    #[test]
+    fn configured_location_applies_to_the_sensors() {
+        let conn = open();
+
+        let config = Config {
+            devices: vec![DeviceConfig {
+                id: "kitchen".to_string(),
+                name: Some("Kitchen sensor".to_string()),
+                location: Some("Kitchen".to_string()),
+                api_key: None,
+            }],
+            ..Config::default()
+        };
+        apply_config(&conn, &config).unwrap();
+        ensure_sensor(&conn, &config.sensors, "kitchen", "humidity").unwrap();
+
+        let devices = query_devices(&conn, &config.sensors).unwrap();
+        let kitchen = devices
+            .iter()
+            .find(|device| device.id == "kitchen")
+            .unwrap();
+        assert_eq!(kitchen.name, "Kitchen sensor");
+        assert!(kitchen
+            .sensors
+            .iter()
+            .all(|sensor| sensor.location.as_deref() == Some("Kitchen")));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::db::DbError;
+
+/// Error returned by the request handlers, rendered as a JSON body of the
+/// form `{"status": 502, "error": "..."}`.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request is malformed (invalid query parameter, ...).
+    BadRequest(String),
+    /// Missing or invalid credentials.
+    Unauthorized(String),
+    /// The account lacks the role required by the request.
+    Forbidden(String),
+    NotFound(String),
+    Database(DbError),
+    /// An external service (the weather API) failed or answered garbage.
+    Upstream(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Database(err) if err.is_unavailable() => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::Upstream(msg)
+            | AppError::Internal(msg) => f.write_str(msg),
+            AppError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<DbError> for AppError {
+    fn from(err: DbError) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            eprintln!("error: {self}");
+        }
+
+        let body = json!({
+            "status": status.as_u16(),
+            "error": self.to_string(),
+        });
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, extract::Query, http::Uri};
+    use rusqlite::ffi;
+    use serde_json::Value;
+
+    use super::*;
+
+    async fn respond(err: AppError) -> (StatusCode, Value) {
+        let response = err.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), 1024).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn sqlite_error(code: i32) -> AppError {
+        DbError::from(rusqlite::Error::SqliteFailure(ffi::Error::new(code), None)).into()
+    }
+
+    #[tokio::test]
+    async fn each_variant_has_its_status_and_a_json_body() {
+        for (err, status, message) in [
+            (
+                AppError::BadRequest("`limit` must be a number".to_string()),
+                StatusCode::BAD_REQUEST,
+                "`limit` must be a number",
+            ),
+            (
+                AppError::Unauthorized("login required".to_string()),
+                StatusCode::UNAUTHORIZED,
+                "login required",
+            ),
+            (
+                AppError::Forbidden("admin role required".to_string()),
+                StatusCode::FORBIDDEN,
+                "admin role required",
+            ),
+            (
+                AppError::NotFound("no device attic".to_string()),
+                StatusCode::NOT_FOUND,
+                "no device attic",
+            ),
+            (
+                sqlite_error(ffi::SQLITE_BUSY),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "database error: Error code 5: The database file is locked",
+            ),
+            (
+                sqlite_error(ffi::SQLITE_CORRUPT),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database error: Error code 11: The database disk image is malformed",
+            ),
+            (
+                AppError::Upstream("open-meteo answered 500".to_string()),
+                StatusCode::BAD_GATEWAY,
+                "open-meteo answered 500",
+            ),
+            (
+                AppError::Internal("cannot hash the password".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "cannot hash the password",
+            ),
+        ] {
+            assert_eq!(err.status(), status, "{err}");
+            assert_eq!(
+                respond(err).await,
+                (
+                    status,
+                    json!({ "status": status.as_u16(), "error": message })
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejections_are_bad_requests() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Params {
+            #[allow(dead_code)]
+            limit: u32,
+        }
+
+        let uri: Uri = "/data?limit=ten".parse().unwrap();
+        let err = AppError::from(Query::<Params>::try_from_uri(&uri).unwrap_err());
+        let (status, body) = respond(err).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["status"], 400);
+        assert!(body["error"].as_str().unwrap().contains("limit"), "{body}");
+    }
+}
@@ -0,0 +1,399 @@
+use std::io;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{rejection::QueryRejection, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    config::SensorConfig,
+    data::{check_range, parse_timestamp_param, select_sensor_data, table_sensors, SensorData},
+    db::{self, DbPool},
+    error::AppError,
+    state::AppState,
+};
+
+/// Number of rows read and formatted into each chunk of the response body.
+const ROWS_PER_CHUNK: usize = 256;
+/// Number of chunks buffered ahead of a slow client.
+const CHUNKS_BUFFERED: usize = 4;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+    /// Indented JSON array.
+    Json,
+}
+
+/// Query parameters accepted by `/export`.
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    fn header(self, sensors: &[SensorConfig]) -> String {
+        match self {
+            ExportFormat::Csv => {
+                let mut header = String::new();
+                let names = std::iter::once("timestamp")
+                    .chain(table_sensors(sensors).map(|sensor| sensor.name.as_str()));
+                for (i, name) in names.enumerate() {
+                    if i > 0 {
+                        header.push(',');
+                    }
+                    push_csv_field(&mut header, name);
+                }
+                header.push('\n');
+                header
+            }
+            ExportFormat::Ndjson => String::new(),
+            ExportFormat::Json => "[".to_string(),
+        }
+    }
+
+    fn footer(self, empty: bool) -> &'static str {
+        match self {
+            ExportFormat::Csv | ExportFormat::Ndjson => "",
+            ExportFormat::Json if empty => "]\n",
+            ExportFormat::Json => "\n]\n",
+        }
+    }
+
+    fn write_row(
+        self,
+        out: &mut String,
+        sensors: &[SensorConfig],
+        row: &SensorData,
+        first: bool,
+    ) -> serde_json::Result<()> {
+        match self {
+            ExportFormat::Csv => {
+                push_csv_field(out, &row.timestamp);
+                // Missing values are left empty.
+                for sensor in table_sensors(sensors) {
+                    out.push(',');
+                    if let Some(value) = row.value(&sensor.name) {
+                        out.push_str(&value.to_string());
+                    }
+                }
+                out.push('\n');
+            }
+            ExportFormat::Ndjson => {
+                out.push_str(&serde_json::to_string(row)?);
+                out.push('\n');
+            }
+            ExportFormat::Json => {
+                out.push_str(if first { "\n" } else { ",\n" });
+                let json = serde_json::to_string_pretty(row)?;
+                for (i, line) in json.lines().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                    }
+                    out.push_str("  ");
+                    out.push_str(line);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Downloads the sensor data in the given time range, oldest first.
+///
+/// Rows are read in pages and sent in chunks, so that exporting a long range
+/// doesn't load it all in memory. Each page takes a connection from the pool
+/// and releases it before waiting for the client, so that a slow download
+/// neither holds a connection nor keeps a read transaction open.
+pub async fn export_data(
+    State(state): State<AppState>,
+    query: Result<Query<ExportQuery>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let Query(query) = query?;
+    let from = parse_timestamp_param("from", query.from.as_deref())?;
+    let to = parse_timestamp_param("to", query.to.as_deref())?;
+    check_range(&from, &to)?;
+    let format = query.format;
+
+    // The first page is read here, so that an unavailable database is
+    // reported before the response starts.
+    let range = Range { from, to };
+    let config = state.config.clone();
+    let first_range = range.clone();
+    let first_page = db::run(&state.db, move |conn| {
+        query_page(conn, &config.sensors, &first_range, None)
+    })
+    .await?;
+
+    let (sender, receiver) = mpsc::channel(CHUNKS_BUFFERED);
+    let pool = state.db.clone();
+    let config = state.config.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = write_rows(&pool, &config.sensors, format, &range, first_page, &sender) {
+            eprintln!("error: export failed: {err}");
+            // Aborts the response, so that the client doesn't take a
+            // truncated file for a complete one.
+            let _ = sender.blocking_send(Err(io::Error::other(err.to_string())));
+        }
+    });
+
+    let filename = format!("sensor-data.{}", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response())
+}
+
+/// Bounds of the exported rows, in the database format.
+#[derive(Clone)]
+struct Range {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// Formats the pages of rows into chunks sent to `sender`, until they are
+/// all sent or the client disconnects.
+fn write_rows(
+    pool: &DbPool,
+    sensors: &[SensorConfig],
+    format: ExportFormat,
+    range: &Range,
+    first_page: Vec<(i64, SensorData)>,
+    sender: &mpsc::Sender<io::Result<Bytes>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chunk = format.header(sensors);
+    let mut count = 0;
+    let mut rows = first_page;
+    loop {
+        for (_, row) in &rows {
+            format.write_row(&mut chunk, sensors, row, count == 0)?;
+            count += 1;
+        }
+        if rows.len() < ROWS_PER_CHUNK {
+            break;
+        }
+        let full = std::mem::take(&mut chunk);
+        if sender.blocking_send(Ok(Bytes::from(full))).is_err() {
+            // The client went away.
+            return Ok(());
+        }
+        let after = rows
+            .last()
+            .map(|(rowid, row)| (row.timestamp.clone(), *rowid));
+        // The connection goes back to the pool before the next chunk is sent.
+        let conn = pool.get()?;
+        rows = query_page(&conn, sensors, range, after)?;
+    }
+    chunk.push_str(format.footer(count == 0));
+    let _ = sender.blocking_send(Ok(Bytes::from(chunk)));
+    Ok(())
+}
+
+/// Returns up to [`ROWS_PER_CHUNK`] rows of the range with their rowid,
+/// following the `(timestamp, rowid)` of the last row of the previous page.
+fn query_page(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    range: &Range,
+    after: Option<(String, i64)>,
+) -> rusqlite::Result<Vec<(i64, SensorData)>> {
+    let rowid_column = table_sensors(sensors).count() + 1;
+    let (after, after_rowid) = after.unzip();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}, rowid \
+         FROM SensorData \
+         WHERE (?1 IS NULL OR timestamp >= ?1) \
+           AND (?2 IS NULL OR timestamp <= ?2) \
+           AND (?3 IS NULL OR (timestamp, rowid) > (?3, ?4)) \
+         ORDER BY timestamp ASC, rowid ASC \
+         LIMIT ?5",
+        select_sensor_data(sensors)
+    ))?;
+    let rows = stmt.query_map(
+        params![range.from, range.to, after, after_rowid, ROWS_PER_CHUNK],
+        |row| Ok((row.get(rowid_column)?, SensorData::from_row(row, sensors)?)),
+    )?;
+    rows.collect()
+}
+
+/// Appends a CSV field, quoted when it holds a separator, a quote or a line
+/// break.
+fn push_csv_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::config::Config;
+
+    /// Database file with the schema of the sensor logger, holding 300 rows
+    /// on 2026-10-16 from 10:00:00, three per second so that a page ends in
+    /// the middle of a second, and one before and after that day.
+    fn test_db(name: &str) -> (DbPool, PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "pi-home-dashboard-export-{name}-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::file(&path))
+            .unwrap();
+        let mut conn = pool.get().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE SensorData (
+                 timestamp TEXT,
+                 bmp280_temperature REAL,
+                 bmp280_pressure REAL,
+                 htu21d_temperature REAL,
+                 htu21d_humidity REAL
+             );
+             INSERT INTO SensorData VALUES ('2026-10-15 23:59:59', 19, 1010, 19.5, 50);
+             INSERT INTO SensorData VALUES ('2026-10-17 00:00:00', 19, 1010, 19.5, 50);",
+        )
+        .unwrap();
+        let tx = conn.transaction().unwrap();
+        for i in 0..300 {
+            let second = i / 3;
+            tx.execute(
+                "INSERT INTO SensorData VALUES (?1, ?2, 1012, 21.5, 55)",
+                params![
+                    format!("2026-10-16 10:{:02}:{:02}", second / 60, second % 60),
+                    20.0 + f64::from(i % 3)
+                ],
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+        drop(conn);
+        (pool, path)
+    }
+
+    fn export(pool: &DbPool, format: ExportFormat, from: &str, to: &str) -> String {
+        let sensors = Config::default().sensors;
+        let range = Range {
+            from: Some(from.to_string()),
+            to: Some(to.to_string()),
+        };
+        let first_page = query_page(&pool.get().unwrap(), &sensors, &range, None).unwrap();
+        let (sender, mut receiver) = mpsc::channel(16);
+        write_rows(pool, &sensors, format, &range, first_page, &sender).unwrap();
+        drop(sender);
+
+        let mut out = Vec::new();
+        while let Some(chunk) = receiver.blocking_recv() {
+            out.extend_from_slice(&chunk.unwrap());
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_export_is_paged_and_escaped() {
+        let (pool, path) = test_db("csv");
+        pool.get()
+            .unwrap()
+            .execute(
+                "INSERT INTO SensorData VALUES ('2026-10-16 12:00:00, \"noon\"', 22, 1011, 22.5, NULL)",
+                [],
+            )
+            .unwrap();
+
+        let csv = export(
+            &pool,
+            ExportFormat::Csv,
+            "2026-10-16 00:00:00",
+            "2026-10-16 23:59:59",
+        );
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "timestamp,bmp280_temp,bmp280_pressure,htu21d_temp,htu21d_humidity"
+        );
+        // Rows sharing a timestamp across pages are all kept, in order.
+        assert_eq!(lines.len(), 1 + 300 + 1);
+        assert_eq!(lines[1], "2026-10-16 10:00:00,20,1012,21.5,55");
+        assert_eq!(lines[3], "2026-10-16 10:00:00,22,1012,21.5,55");
+        assert_eq!(lines[257], "2026-10-16 10:01:25,21,1012,21.5,55");
+        assert_eq!(lines[258], "2026-10-16 10:01:25,22,1012,21.5,55");
+        assert_eq!(lines[300], "2026-10-16 10:01:39,22,1012,21.5,55");
+        assert_eq!(
+            lines[301],
+            r#""2026-10-16 12:00:00, ""noon""",22,1011,22.5,"#
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn json_export_is_filtered_by_range() {
+        let (pool, path) = test_db("json");
+
+        let json = export(
+            &pool,
+            ExportFormat::Json,
+            "2026-10-16 10:00:01",
+            "2026-10-16 10:00:02",
+        );
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0]["timestamp"], "2026-10-16 10:00:01");
+        assert_eq!(rows[0]["bmp280_temp"], 20.0);
+        assert_eq!(rows[5]["timestamp"], "2026-10-16 10:00:02");
+        assert_eq!(rows[5]["bmp280_temp"], 22.0);
+
+        let json = export(
+            &pool,
+            ExportFormat::Json,
+            "2026-10-18 00:00:00",
+            "2026-10-18 23:59:59",
+        );
+        assert_eq!(json, "[]\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}
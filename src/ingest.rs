@@ -0,0 +1,421 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::{
+    config::{DatabaseConfig, DeviceConfig, SensorConfig},
+    data::{db_now, normalize_timestamp, DB_TIMESTAMP_FORMAT},
+    db::{self, DbPool},
+    devices,
+    error::AppError,
+    state::AppState,
+};
+
+/// Number of readings waiting to be written before new ones are dropped.
+const QUEUE_CAPACITY: usize = 256;
+/// Maximum number of readings written in one transaction.
+const BATCH_SIZE: usize = 64;
+/// Maximum length of the device ids and measurement names.
+const MAX_NAME_LEN: usize = 64;
+/// Maximum number of readings accepted by one `POST /api/readings`.
+const MAX_READINGS_PER_REQUEST: usize = 1000;
+
+/// Measurements sent by another device, taken at the same time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceReading {
+    pub device: String,
+    pub timestamp: String,
+    pub values: Vec<(String, f64)>,
+}
+
+/// Parses a JSON object of measurements, such as
+/// `{"temperature": 21.4, "humidity": 48}`.
+///
+/// A measurement may also be given with its unit, as in
+/// `{"temperature": {"value": 21.4, "unit": "°C"}}`. The measurements of the
+/// sensor registry are checked against its unit and valid range.
+///
+/// The optional `timestamp` field is either in one of the formats accepted
+/// by [`normalize_timestamp`], or a number of seconds since the Unix epoch;
+/// it defaults to the current time.
+pub fn parse_reading(
+    device: &str,
+    payload: &[u8],
+    config: &DatabaseConfig,
+    sensors: &[SensorConfig],
+) -> Result<DeviceReading, String> {
+    let fields =
+        serde_json::from_slice(payload).map_err(|err| format!("invalid JSON object: {err}"))?;
+    parse_fields(device, fields, config, sensors)
+}
+
+fn parse_fields(
+    device: &str,
+    fields: serde_json::Map<String, Value>,
+    config: &DatabaseConfig,
+    sensors: &[SensorConfig],
+) -> Result<DeviceReading, String> {
+    if !is_valid_name(device) {
+        return Err(format!("invalid device id {device:?}"));
+    }
+    if device == devices::LOCAL_DEVICE {
+        return Err(format!("device id {device:?} is reserved"));
+    }
+
+    let mut timestamp = None;
+    let mut values = Vec::new();
+    for (name, value) in fields {
+        if name == "timestamp" {
+            timestamp = Some(parse_timestamp(&value, config)?);
+            continue;
+        }
+        if !is_valid_name(&name) {
+            return Err(format!("invalid measurement name {name:?}"));
+        }
+        let value = parse_value(&name, &value, sensors)?;
+        values.push((name, value));
+    }
+    if values.is_empty() {
+        return Err("no measurement in the reading".to_string());
+    }
+
+    Ok(DeviceReading {
+        device: device.to_string(),
+        timestamp: timestamp
+            .unwrap_or_else(|| db_now(config).format(DB_TIMESTAMP_FORMAT).to_string()),
+        values,
+    })
+}
+
+/// Parses a number, or an object holding a `value` and its `unit`.
+fn parse_value(name: &str, value: &Value, sensors: &[SensorConfig]) -> Result<f64, String> {
+    let (number, unit) = match value {
+        Value::Object(fields) => {
+            if let Some(field) = fields
+                .keys()
+                .find(|field| *field != "value" && *field != "unit")
+            {
+                return Err(format!("unknown field `{field}` in `{name}`"));
+            }
+            let unit = match fields.get("unit") {
+                None => None,
+                Some(Value::String(unit)) => Some(unit.as_str()),
+                Some(unit) => return Err(format!("unit of `{name}` must be a string, got {unit}")),
+            };
+            (fields.get("value").and_then(Value::as_f64), unit)
+        }
+        value => (value.as_f64(), None),
+    };
+    let value = match number {
+        Some(value) if value.is_finite() => value,
+        _ => return Err(format!("`{name}` must be a number, got {value}")),
+    };
+
+    let Some(sensor) = sensors.iter().find(|sensor| sensor.name == name) else {
+        return Ok(value);
+    };
+    if let Some(unit) = unit {
+        if !sensor.unit.is_empty() && unit != sensor.unit {
+            return Err(format!("`{name}` must be in {}, got {unit}", sensor.unit));
+        }
+    }
+    if !sensor.is_valid(value) {
+        let range = match (sensor.min, sensor.max) {
+            (Some(min), Some(max)) => format!("between {min} and {max}"),
+            (Some(min), None) => format!("at least {min}"),
+            (None, _) => format!("at most {}", sensor.max.unwrap_or_default()),
+        };
+        return Err(format!(
+            "`{name}` is {value}, but plausible values are {range}"
+        ));
+    }
+    Ok(value)
+}
+
+fn parse_timestamp(value: &Value, config: &DatabaseConfig) -> Result<String, String> {
+    let timestamp = match value {
+        Value::String(value) => normalize_timestamp(value),
+        Value::Number(secs) => secs
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|timestamp| {
+                if config.utc_timestamps {
+                    timestamp.naive_utc()
+                } else {
+                    timestamp.with_timezone(&Local).naive_local()
+                }
+            })
+            .map(|timestamp| timestamp.format(DB_TIMESTAMP_FORMAT).to_string()),
+        _ => None,
+    };
+    timestamp.ok_or_else(|| format!("invalid `timestamp` {value}"))
+}
+
+/// Device ids and measurement names are made of ASCII letters, digits, `_`,
+/// `-` and `.`.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'))
+}
+
+/// Stores the readings sent by a device, authenticated by its API key
+/// (`Authorization: Bearer <key>`).
+///
+/// The body is a reading as accepted by [`parse_reading`], or an array of
+/// them. The readings are stored in a single transaction: none is stored if
+/// one of them is invalid.
+pub async fn post_readings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<Value>, JsonRejection>,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    let device = authenticate(&state.config.devices, &headers)?.id.clone();
+    let Json(body) = body?;
+
+    let objects = match body {
+        Value::Array(objects) => objects,
+        object => vec![object],
+    };
+    if objects.is_empty() || objects.len() > MAX_READINGS_PER_REQUEST {
+        return Err(AppError::BadRequest(format!(
+            "expected between 1 and {MAX_READINGS_PER_REQUEST} readings, got {}",
+            objects.len()
+        )));
+    }
+    let readings = objects
+        .into_iter()
+        .enumerate()
+        .map(|(i, object)| {
+            let Value::Object(fields) = object else {
+                return Err(format!("reading {i}: expected a JSON object, got {object}"));
+            };
+            parse_fields(
+                &device,
+                fields,
+                &state.config.database,
+                &state.config.sensors,
+            )
+            .map_err(|err| format!("reading {i}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AppError::BadRequest)?;
+
+    let count = readings.len();
+    let config = state.config.clone();
+    db::run(&state.writer, move |conn| {
+        insert_readings(conn, &config.sensors, &readings)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "device": device, "readings": count })),
+    ))
+}
+
+/// Returns the device whose API key is given as bearer token.
+fn authenticate<'a>(
+    devices: &'a [DeviceConfig],
+    headers: &HeaderMap,
+) -> Result<&'a DeviceConfig, AppError> {
+    let key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized("missing API key".to_string()))?;
+
+    devices
+        .iter()
+        .find(|device| {
+            device
+                .api_key
+                .as_deref()
+                .is_some_and(|expected| keys_match(expected, key.trim()))
+        })
+        .ok_or_else(|| AppError::Unauthorized("invalid API key".to_string()))
+}
+
+/// Compares keys in a time independent of the position of the first
+/// difference, so that it doesn't leak how much of a guess is right.
+fn keys_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Starts the task writing the readings sent to the returned queue into the
+/// `readings` table.
+pub fn spawn_writer(writer: DbPool, sensors: Vec<SensorConfig>) -> mpsc::Sender<DeviceReading> {
+    let sensors: Arc<[SensorConfig]> = sensors.into();
+    let (tx, mut rx) = mpsc::channel(QUEUE_CAPACITY);
+    tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        while rx.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+            let readings = std::mem::take(&mut batch);
+            let sensors = sensors.clone();
+            if let Err(err) = db::run(&writer, move |conn| {
+                insert_readings(conn, &sensors, &readings)
+            })
+            .await
+            {
+                eprintln!("error: cannot store device readings: {err}");
+            }
+        }
+    });
+    tx
+}
+
+/// Inserts readings in a single transaction, creating the devices and
+/// sensors seen for the first time. A reading replaces the value of the same
+/// sensor at the same time.
+pub fn insert_readings(
+    conn: &Connection,
+    sensors: &[SensorConfig],
+    readings: &[DeviceReading],
+) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT OR REPLACE INTO readings (sensor_id, ts, value) VALUES (?1, ?2, ?3)",
+        )?;
+        for reading in readings {
+            for (measurement, value) in &reading.values {
+                let sensor = devices::ensure_sensor(&tx, sensors, &reading.device, measurement)?;
+                stmt.execute(params![sensor, reading.timestamp, value])?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn reading_is_parsed_with_its_timestamp() {
+        let config = DatabaseConfig::default();
+
+        let reading = parse_reading(
+            "living-room",
+            br#"{"temperature": 21.5, "humidity": 48, "timestamp": "2026-10-16T10:00"}"#,
+            &config,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            reading,
+            DeviceReading {
+                device: "living-room".to_string(),
+                timestamp: "2026-10-16 10:00:00".to_string(),
+                values: vec![
+                    ("humidity".to_string(), 48.0),
+                    ("temperature".to_string(), 21.5),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_readings_are_rejected() {
+        let config = DatabaseConfig::default();
+
+        for payload in [
+            &br#"[21.5]"#[..],
+            br#"{}"#,
+            br#"{"temperature": "warm"}"#,
+            br#"{"temperature": 21.5, "timestamp": "yesterday"}"#,
+            br#"{"temp'; DROP TABLE": 21.5}"#,
+        ] {
+            assert!(parse_reading("kitchen", payload, &config, &[]).is_err());
+        }
+        assert!(parse_reading("kitchen/2", br#"{"temperature": 21.5}"#, &config, &[]).is_err());
+        assert!(parse_reading("local", br#"{"temperature": 21.5}"#, &config, &[]).is_err());
+    }
+
+    #[test]
+    fn readings_are_checked_against_the_registry() {
+        let config = DatabaseConfig::default();
+        let sensors = Config::default().sensors;
+
+        let reading = parse_reading(
+            "kitchen",
+            r#"{"temperature": {"value": 21.5, "unit": "°C"}, "co2": 640, "voc": 12}"#.as_bytes(),
+            &config,
+            &sensors,
+        )
+        .unwrap();
+        assert_eq!(reading.values.len(), 3);
+
+        for (payload, error) in [
+            (
+                r#"{"temperature": {"value": 70.7, "unit": "°F"}}"#,
+                "`temperature` must be in °C, got °F",
+            ),
+            (
+                r#"{"humidity": 118}"#,
+                "`humidity` is 118, but plausible values are between 0 and 100",
+            ),
+            (
+                r#"{"humidity": {"value": 48, "units": "%"}}"#,
+                "unknown field `units` in `humidity`",
+            ),
+        ] {
+            assert_eq!(
+                parse_reading("kitchen", payload.as_bytes(), &config, &sensors).unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn device_is_found_by_its_api_key() {
+        let device = |id: &str, api_key: Option<&str>| DeviceConfig {
+            id: id.to_string(),
+            name: None,
+            location: None,
+            api_key: api_key.map(str::to_string),
+        };
+        let devices = [
+            device("local", None),
+            device("kitchen", Some("kitchen-0123456789abcdef")),
+            device("garage", Some("garage-0123456789abcdef")),
+        ];
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        let found = authenticate(&devices, &headers("Bearer garage-0123456789abcdef")).unwrap();
+        assert_eq!(found.id, "garage");
+        for value in [
+            "Bearer garage-0123456789abcdeF",
+            "Bearer garage",
+            "Bearer ",
+            "garage-0123456789abcdef",
+        ] {
+            assert!(matches!(
+                authenticate(&devices, &headers(value)),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+        assert!(authenticate(&devices, &HeaderMap::new()).is_err());
+    }
+}
@@ -1,98 +1,261 @@
-use axum::{response::Html, routing::get, Json, Router};
-use reqwest;
-use rusqlite::Connection;
-use serde::Serialize;
-use tokio::fs;
+mod alerts;
+mod assets;
+mod auth;
+mod config;
+mod data;
+mod db;
+mod devices;
+mod error;
+mod export;
+mod ingest;
+mod metrics;
+mod mqtt;
+mod notify;
+mod state;
+mod stream;
+mod templates;
+mod tls;
+mod weather;
 
-const DB_FILE: &str = "/var/lib/pi-home-sensors_data/data.db";
-const PI_HOME_DASHBOARD_RES: &str = "/usr/share/pi-home-dashboard/templates";
+use std::{sync::Arc, time::Duration};
 
-#[derive(Serialize)]
-struct SensorData {
-    timestamp: String,
-    bmp280_temp: f32,
-    bmp280_pressure: f32,
-    htu21d_temp: f32,
-    htu21d_humidity: f32,
-}
+use axum::{
+    extract::State,
+    http::{HeaderMap, Uri},
+    middleware,
+    response::Response,
+    routing::{get, post},
+    Router,
+};
+use clap::Parser;
 
-#[derive(Serialize)]
-struct Weather {
-    external_temp: f32,
-    external_windspeed: f32,
-    external_time: String,
-}
+use crate::{
+    alerts::{acknowledge_alert, get_alert_history, get_alerts},
+    config::{Cli, Command, Config},
+    data::{get_aggregated_data, get_data, get_latest_data, get_summary},
+    devices::{get_device_data, get_devices},
+    error::AppError,
+    export::export_data,
+    ingest::post_readings,
+    metrics::{metrics, track_requests, Metrics},
+    state::AppState,
+    stream::stream_data,
+    weather::{external_weather, external_weather_history, WeatherCache},
+};
+
+/// Timeout of the requests made to external services.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() {
-    let app = Router::new()
-        .route("/", get(index))
-        .route("/data", get(get_data))
-        .route("/external-weather", get(external_weather));
+    let mut cli = Cli::parse();
+    let command = cli.command.take();
+    let config = match Config::load(cli) {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
 
-    // Run app, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
+    if let Some(dir) = config.database.dashboard_path.parent() {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            eprintln!("error: cannot create {}: {err}", dir.display());
+            std::process::exit(1);
+        }
+    }
+    let writer = match db::open_writer(&config.database) {
+        Ok(writer) => writer,
+        Err(err) => {
+            eprintln!(
+                "error: cannot open database {} with {} attached: {err}",
+                config.database.dashboard_path.display(),
+                config.database.path.display()
+            );
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = db::run(&writer, db::create_schema).await {
+        eprintln!("error: cannot create the database schema: {err}");
+        std::process::exit(1);
+    }
+    // The read-only connections need the dashboard database to exist.
+    let db = match db::open_pool(&config.database) {
+        Ok(db) => db,
+        Err(err) => {
+            eprintln!(
+                "error: cannot open database {} with {} attached: {err}",
+                config.database.dashboard_path.display(),
+                config.database.path.display()
+            );
+            std::process::exit(1);
+        }
+    };
+    if let Some(Command::Users(command)) = command {
+        if let Err(err) = auth::run_command(&writer, command).await {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    let pages = match templates::Pages::render(&config).await {
+        Ok(pages) => Arc::new(pages),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
+    if config.auth.enabled {
+        match db::run(&db, auth::has_users).await {
+            Ok(true) => {}
+            Ok(false) => eprintln!(
+                "warning: authentication is enabled but there is no account, \
+                 create one with `pi-home-dashboard users set <name> --role admin`"
+            ),
+            Err(err) => {
+                eprintln!("error: cannot read the accounts: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    let registry = config.clone();
+    match db::run(&db, move |conn| {
+        data::missing_columns(conn, &registry.sensors)
+    })
+    .await
+    {
+        Ok(missing) if missing.is_empty() => {}
+        Ok(missing) => {
+            eprintln!(
+                "error: the sensor registry refers to missing SensorData columns: {}",
+                missing.join(", ")
+            );
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("error: cannot read the SensorData columns: {err}");
+            std::process::exit(1);
+        }
+    }
+    let device_config = config.clone();
+    if let Err(err) = db::run(&writer, move |conn| {
+        devices::apply_config(conn, &device_config)
+    })
+    .await
+    {
+        eprintln!("error: cannot store the device settings: {err}");
+        std::process::exit(1);
+    }
 
-async fn index() -> Html<String> {
-    let html = fs::read_to_string(format!("{PI_HOME_DASHBOARD_RES}/index.html"))
-        .await
+    let http = reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        // MET Norway rejects requests without an identifying User-Agent.
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
         .unwrap();
-    Html(html)
-}
-
-async fn get_data() -> Json<Vec<SensorData>> {
-    let conn = Connection::open(DB_FILE).unwrap();
-    let mut sensors = Vec::new();
+    let sensor_feed = stream::spawn_feed(
+        db.clone(),
+        config.sensors.clone(),
+        Duration::from_secs(config.database.poll_interval_secs),
+    );
+    let state = AppState {
+        config: config.clone(),
+        db,
+        writer,
+        sensor_feed,
+        weather: Arc::new(WeatherCache::new(
+            weather::build_provider(&config.weather, &http),
+            Duration::from_secs(config.weather.cache_ttl_secs),
+        )),
+        metrics: Arc::new(Metrics::default()),
+        pages,
+    };
 
-    if let Ok(mut stmt) = conn.prepare(
-        "SELECT timestamp, bmp280_temperature, bmp280_pressure, htu21d_temperature, htu21d_humidity \
-         FROM SensorData ORDER BY timestamp DESC",
-    ) {
-        if let Ok(sensor_iter) = stmt.query_map([], |row| {
-            Ok(SensorData {
-                timestamp: row.get(0)?,
-                bmp280_temp: row.get(1)?,
-                bmp280_pressure: row.get(2)?,
-                htu21d_temp: row.get(3)?,
-                htu21d_humidity: row.get(4)?,
-            })
-        }) {
-            for sensor in sensor_iter {
-                if let Ok(sensor) = sensor {
-                    sensors.push(sensor);
-                }
-            }
+    weather::spawn_poller(state.clone());
+    let notifications = match notify::spawn_dispatchers(config.clone(), state.writer.clone(), &http)
+    {
+        Ok(notifications) => notifications,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
         }
-    }
+    };
+    alerts::spawn_engine(state.clone(), notifications);
+    mqtt::spawn_client(&state);
 
-    Json(sensors)
-}
+    let mut app = Router::new()
+        .route("/", get(index))
+        .route("/static/{*path}", get(assets::static_file))
+        .route("/data", get(get_data))
+        .route("/data/aggregate", get(get_aggregated_data))
+        .route("/data/stream", get(stream_data))
+        .route("/data/latest", get(get_latest_data))
+        .route("/data/summary", get(get_summary))
+        .route("/devices", get(get_devices))
+        .route("/devices/{id}/data", get(get_device_data))
+        .route("/export", get(export_data))
+        .route("/external-weather", get(external_weather))
+        .route("/external-weather/history", get(external_weather_history))
+        .route("/api/readings", post(post_readings))
+        .route("/alerts", get(get_alerts))
+        .route("/alerts/history", get(get_alert_history))
+        .route("/alerts/{id}/acknowledge", post(acknowledge_alert))
+        .route("/metrics", get(metrics));
+    if config.auth.enabled {
+        app = app
+            .merge(auth::routes())
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::require_login,
+            ));
+    }
+    let app = app
+        .fallback(not_found)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
+        .with_state(state);
 
-async fn external_weather() -> Json<Weather> {
-    let url =
-        "https://api.open-meteo.com/v1/forecast?latitude=48.85&longitude=2.35&current_weather=true";
+    if !config.tls.enabled {
+        let listener = tokio::net::TcpListener::bind(config.server.bind)
+            .await
+            .unwrap();
+        axum::serve(listener, app).await.unwrap();
+        return;
+    }
 
-    match reqwest::get(url).await {
-        Ok(response) if response.status().is_success() => {
-            if let Ok(json) = response.json::<serde_json::Value>().await {
-                let weather = &json["current_weather"];
-                let weather_data = Weather {
-                    external_temp: weather["temperature"].as_f64().unwrap_or(0.0) as f32,
-                    external_windspeed: weather["windspeed"].as_f64().unwrap_or(0.0) as f32,
-                    external_time: weather["time"].as_str().unwrap_or("N/A").to_string(),
-                };
-                return Json(weather_data);
-            }
+    let tls = match tls::load(&config.tls).await {
+        Ok(tls) => tls,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
         }
-        _ => {}
+    };
+    if let Err(err) = tls::spawn_reloader(tls.clone(), config.tls.clone()) {
+        eprintln!("error: cannot watch SIGHUP: {err}");
+        std::process::exit(1);
     }
+    if let Some(redirect_bind) = config.tls.redirect_bind {
+        if let Err(err) = tls::spawn_redirect(redirect_bind, config.server.bind.port()).await {
+            eprintln!("error: cannot listen on {redirect_bind}: {err}");
+            std::process::exit(1);
+        }
+    }
+    axum_server::bind_rustls(config.server.bind, tls)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
 
-    // Fallback: return default weather data if any step fails
-    Json(Weather {
-        external_temp: 0.0,
-        external_windspeed: 0.0,
-        external_time: "N/A".to_string(),
-    })
+async fn index(State(state): State<AppState>, headers: HeaderMap) -> Result<Response, AppError> {
+    assets::page(&state, &headers, "index.html").await
+}
+
+async fn not_found(uri: Uri) -> AppError {
+    AppError::NotFound(format!("no route for {uri}"))
 }
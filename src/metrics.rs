@@ -0,0 +1,351 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    config::SensorConfig,
+    data::{query_latest, reading_age, table_sensors, SensorData},
+    db,
+    state::AppState,
+};
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Prefix of all the exported metric names.
+const PREFIX: &str = "pi_home";
+/// Names of the metrics exported besides the sensor gauges, which the
+/// sensors can't use.
+pub const RESERVED_NAMES: [&str; 7] = [
+    "database_up",
+    "sensor_data_age_seconds",
+    "outdoor_temperature_celsius",
+    "outdoor_windspeed_kmh",
+    "weather_fetch_failures_total",
+    "http_requests_total",
+    "http_request_duration_seconds",
+];
+
+/// HTTP request counters, filled by [`track_requests`] and exported by
+/// `/metrics`.
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<RouteKey, RouteStats>>,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct RouteKey {
+    method: String,
+    route: String,
+}
+
+#[derive(Default)]
+struct RouteStats {
+    /// Number of responses by status code.
+    statuses: BTreeMap<u16, u64>,
+    /// Number of requests by latency bucket, not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    duration_sum: f64,
+}
+
+impl Metrics {
+    fn record(&self, key: RouteKey, status: u16, seconds: f64) {
+        let mut requests = self.requests.lock().unwrap();
+        let stats = requests.entry(key).or_default();
+        *stats.statuses.entry(status).or_default() += 1;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&le| seconds <= le) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.count += 1;
+        stats.duration_sum += seconds;
+    }
+
+    fn write_requests(&self, out: &mut String) {
+        let requests = self.requests.lock().unwrap();
+
+        write_header(
+            out,
+            "http_requests_total",
+            "counter",
+            "HTTP requests served, by route and status.",
+        );
+        for (key, stats) in requests.iter() {
+            for (status, count) in &stats.statuses {
+                let _ = writeln!(
+                    out,
+                    "{PREFIX}_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+                    key.method,
+                    escape_label(&key.route)
+                );
+            }
+        }
+
+        write_header(
+            out,
+            "http_request_duration_seconds",
+            "histogram",
+            "Time spent handling HTTP requests, by route.",
+        );
+        for (key, stats) in requests.iter() {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\"",
+                key.method,
+                escape_label(&key.route)
+            );
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "{PREFIX}_http_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{PREFIX}_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                stats.count
+            );
+            let _ = writeln!(
+                out,
+                "{PREFIX}_http_request_duration_seconds_sum{{{labels}}} {}",
+                stats.duration_sum
+            );
+            let _ = writeln!(
+                out,
+                "{PREFIX}_http_request_duration_seconds_count{{{labels}}} {}",
+                stats.count
+            );
+        }
+    }
+}
+
+/// Middleware counting the requests and their latency per route.
+///
+/// Requests are grouped by route pattern (e.g. `/data/latest`) rather than
+/// by path, and all the unmatched paths are grouped together, so that
+/// scanners can't grow the set of series.
+pub async fn track_requests(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    state.metrics.record(
+        RouteKey { method, route },
+        response.status().as_u16(),
+        start.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    let config = state.config.clone();
+    match db::run(&state.db, move |conn| query_latest(conn, &config.sensors)).await {
+        Ok(latest) => {
+            write_gauge(
+                &mut out,
+                "database_up",
+                "Whether the sensor database can be read.",
+                1.0,
+            );
+            if let Some(reading) = latest {
+                write_sensors(&mut out, &state.config.sensors, &reading);
+                match reading_age(&reading, &state.config.database) {
+                    Ok(age) => write_gauge(
+                        &mut out,
+                        "sensor_data_age_seconds",
+                        "Seconds elapsed since the newest sensor row was recorded.",
+                        age as f64,
+                    ),
+                    Err(err) => eprintln!("error: {err}"),
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("error: cannot read the latest sensor data: {err}");
+            write_gauge(
+                &mut out,
+                "database_up",
+                "Whether the sensor database can be read.",
+                0.0,
+            );
+        }
+    }
+
+    if let Some(weather) = state.weather.last_reading() {
+        write_gauge(
+            &mut out,
+            "outdoor_temperature_celsius",
+            "Latest outdoor temperature from the weather provider.",
+            weather.external_temp.into(),
+        );
+        write_gauge(
+            &mut out,
+            "outdoor_windspeed_kmh",
+            "Latest outdoor wind speed from the weather provider.",
+            weather.external_windspeed.into(),
+        );
+    }
+    write_header(
+        &mut out,
+        "weather_fetch_failures_total",
+        "counter",
+        "Failed fetches from the weather providers.",
+    );
+    let _ = writeln!(
+        out,
+        "{PREFIX}_weather_fetch_failures_total {}",
+        state.weather.fetch_failures()
+    );
+
+    state.metrics.write_requests(&mut out);
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        out,
+    )
+}
+
+/// Writes a gauge per value of the row.
+fn write_sensors(out: &mut String, sensors: &[SensorConfig], reading: &SensorData) {
+    for sensor in table_sensors(sensors) {
+        let Some(value) = reading.value(&sensor.name) else {
+            continue;
+        };
+        let help = if sensor.unit.is_empty() {
+            format!("Latest {} reading.", sensor.label())
+        } else {
+            format!("Latest {} reading, in {}.", sensor.label(), sensor.unit)
+        };
+        write_gauge(out, &sensor.metric_name(), &help, value);
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {PREFIX}_{name} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}_{name} {kind}");
+}
+
+fn write_gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    write_header(out, name, "gauge", help);
+    let _ = writeln!(out, "{PREFIX}_{name} {value}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn co2_sensor() -> SensorConfig {
+        SensorConfig {
+            name: "co2".to_string(),
+            column: Some("scd30_co2".to_string()),
+            label: Some("CO2".to_string()),
+            unit: "ppm".to_string(),
+            chart: None,
+            min: None,
+            max: None,
+            metric: None,
+        }
+    }
+
+    #[test]
+    fn sensors_and_requests_are_exported() {
+        let reading = SensorData {
+            timestamp: "2026-10-16 10:00:00".to_string(),
+            values: [("co2".to_string(), Some(640.0))].into(),
+        };
+        let metrics = Metrics::default();
+        for (status, seconds) in [(200, 0.003), (200, 0.2), (404, 0.003)] {
+            metrics.record(
+                RouteKey {
+                    method: "GET".to_string(),
+                    route: "/say/\"hi\"\\".to_string(),
+                },
+                status,
+                seconds,
+            );
+        }
+
+        let mut out = String::new();
+        write_sensors(&mut out, &[co2_sensor()], &reading);
+        metrics.write_requests(&mut out);
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[..5],
+            [
+                "# HELP pi_home_sensor_co2 Latest CO2 reading, in ppm.",
+                "# TYPE pi_home_sensor_co2 gauge",
+                "pi_home_sensor_co2 640",
+                "# HELP pi_home_http_requests_total HTTP requests served, by route and status.",
+                "# TYPE pi_home_http_requests_total counter",
+            ]
+        );
+        let labels = r#"method="GET",route="/say/\"hi\"\\""#;
+        assert!(lines.contains(
+            &format!(r#"pi_home_http_requests_total{{{labels},status="200"}} 2"#).as_str()
+        ));
+        assert!(lines.contains(
+            &format!(r#"pi_home_http_requests_total{{{labels},status="404"}} 1"#).as_str()
+        ));
+        assert!(lines.contains(&"# TYPE pi_home_http_request_duration_seconds histogram"));
+        assert!(lines.contains(
+            &format!(r#"pi_home_http_request_duration_seconds_bucket{{{labels},le="0.005"}} 2"#)
+                .as_str()
+        ));
+        assert!(lines.contains(
+            &format!(r#"pi_home_http_request_duration_seconds_bucket{{{labels},le="+Inf"}} 3"#)
+                .as_str()
+        ));
+        assert!(lines.contains(
+            &format!(r#"pi_home_http_request_duration_seconds_count{{{labels}}} 3"#).as_str()
+        ));
+    }
+
+    #[test]
+    fn metric_names_must_be_unique() {
+        let config = |metric: Option<&str>| {
+            let mut config = Config::default();
+            let co2 = config
+                .sensors
+                .iter_mut()
+                .find(|sensor| sensor.name == "co2")
+                .unwrap();
+            co2.column = Some("scd30_co2".to_string());
+            co2.metric = metric.map(str::to_string);
+            config
+        };
+
+        config(None).validate().unwrap();
+        assert!(config(Some("bmp280_temperature_celsius"))
+            .validate()
+            .is_err());
+        assert!(config(Some("database_up")).validate().is_err());
+    }
+}
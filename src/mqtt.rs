@@ -0,0 +1,467 @@
+use std::{sync::Arc, time::Duration};
+
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, Publish, QoS};
+use serde_json::json;
+use tokio::{
+    sync::{
+        broadcast::{error::RecvError, Receiver},
+        mpsc,
+    },
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    config::{DatabaseConfig, MqttConfig, SensorConfig},
+    data::{table_sensors, SensorData},
+    ingest::{self, DeviceReading},
+    state::AppState,
+    stream::SensorFeed,
+    weather::WeatherCache,
+};
+
+/// Delay before reconnecting to the broker after an error.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// Number of messages queued while the broker is unreachable.
+const REQUEST_CAPACITY: usize = 64;
+
+/// Sensor announced to Home Assistant.
+struct DiscoveredSensor<'a> {
+    /// Field of the published JSON.
+    key: &'a str,
+    name: &'a str,
+    unit: &'a str,
+    device_class: Option<&'a str>,
+    /// Whether the field comes from the weather topic rather than from the
+    /// sensors topic.
+    outdoor: bool,
+}
+
+/// Fields of the weather topic, the ones of the sensors topic coming from
+/// the sensor registry.
+const OUTDOOR_SENSORS: [DiscoveredSensor<'static>; 4] = [
+    DiscoveredSensor {
+        key: "external_temp",
+        name: "Outdoor temperature",
+        unit: "°C",
+        device_class: Some("temperature"),
+        outdoor: true,
+    },
+    DiscoveredSensor {
+        key: "external_humidity",
+        name: "Outdoor humidity",
+        unit: "%",
+        device_class: Some("humidity"),
+        outdoor: true,
+    },
+    DiscoveredSensor {
+        key: "external_pressure",
+        name: "Outdoor pressure",
+        unit: "hPa",
+        device_class: Some("atmospheric_pressure"),
+        outdoor: true,
+    },
+    DiscoveredSensor {
+        key: "external_windspeed",
+        name: "Wind speed",
+        unit: "km/h",
+        device_class: Some("wind_speed"),
+        outdoor: true,
+    },
+];
+
+/// Readings received on the ingest topic are written by this task.
+struct Ingester {
+    queue: mpsc::Sender<DeviceReading>,
+    database: DatabaseConfig,
+    sensors: Vec<SensorConfig>,
+}
+
+/// Connects to the broker, if enabled, to publish the new sensor rows and
+/// the outdoor weather, and to store the readings of the other devices.
+pub fn spawn_client(state: &AppState) {
+    let config = &state.config.mqtt;
+    if !config.enabled {
+        return;
+    }
+
+    let ingester = (!config.ingest_topic.is_empty()).then(|| Ingester {
+        queue: ingest::spawn_writer(state.writer.clone(), state.config.sensors.clone()),
+        database: state.config.database.clone(),
+        sensors: state.config.sensors.clone(),
+    });
+    spawn(
+        config,
+        &state.config.sensors,
+        &state.sensor_feed,
+        state.weather.clone(),
+        ingester,
+    );
+}
+
+/// Starts the tasks of the client.
+///
+/// The connection is kept up by a task that reconnects after errors: the
+/// availability topic is set to `online` on every connection, and to
+/// `offline` by the broker (as the last will) once it is lost.
+fn spawn(
+    config: &MqttConfig,
+    sensors: &[SensorConfig],
+    feed: &SensorFeed,
+    weather: Arc<WeatherCache>,
+    ingester: Option<Ingester>,
+) {
+    let (client, eventloop) = AsyncClient::new(options(config), REQUEST_CAPACITY);
+    tokio::spawn(drive(
+        client.clone(),
+        eventloop,
+        config.clone(),
+        discovery_configs(config, sensors),
+        ingester,
+    ));
+    tokio::spawn(publish_sensors(
+        client.clone(),
+        feed.subscribe(),
+        config.sensors_topic.clone(),
+    ));
+    if config.weather_interval_secs > 0 {
+        tokio::spawn(publish_weather(client, weather, config.clone()));
+    }
+}
+
+fn options(config: &MqttConfig) -> MqttOptions {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options
+        .set_keep_alive(KEEP_ALIVE)
+        .set_last_will(LastWill::new(
+            &config.availability_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username, password);
+    }
+    options
+}
+
+/// Polls the connection, which also reconnects after errors.
+async fn drive(
+    client: AsyncClient,
+    mut eventloop: EventLoop,
+    config: MqttConfig,
+    discovery_configs: Vec<(String, serde_json::Value)>,
+    ingester: Option<Ingester>,
+) {
+    // Home Assistant publishes `online` there when it starts, and expects
+    // the discovery configs to be sent again.
+    let ha_status_topic = format!("{}/status", config.discovery_prefix);
+    let discovery = !config.discovery_prefix.is_empty();
+
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                publish(&client, &config.availability_topic, "online");
+                if discovery {
+                    publish_discovery(&client, &discovery_configs);
+                    subscribe(&client, &ha_status_topic);
+                }
+                if ingester.is_some() {
+                    subscribe(&client, &config.ingest_topic);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(message)))
+                if message.topic == ha_status_topic && &message.payload[..] == b"online" =>
+            {
+                publish_discovery(&client, &discovery_configs);
+            }
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                if let Some(ingester) = &ingester {
+                    ingest_message(ingester, &config.ingest_topic, &message);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!(
+                    "error: MQTT connection to {}:{} failed: {err}",
+                    config.host, config.port
+                );
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Queues a retained message. Never waits, since it is also called from the
+/// task driving the connection.
+fn publish(client: &AsyncClient, topic: &str, payload: impl Into<Vec<u8>>) {
+    if let Err(err) = client.try_publish(topic, QoS::AtLeastOnce, true, payload) {
+        eprintln!("error: cannot publish to {topic}: {err}");
+    }
+}
+
+fn subscribe(client: &AsyncClient, topic: &str) {
+    if let Err(err) = client.try_subscribe(topic, QoS::AtLeastOnce) {
+        eprintln!("error: cannot subscribe to {topic}: {err}");
+    }
+}
+
+fn ingest_message(ingester: &Ingester, filter: &str, message: &Publish) {
+    let Some(device) = device_id(filter, &message.topic) else {
+        return;
+    };
+    match ingest::parse_reading(
+        device,
+        &message.payload,
+        &ingester.database,
+        &ingester.sensors,
+    ) {
+        Ok(reading) => {
+            if ingester.queue.try_send(reading).is_err() {
+                eprintln!("error: too many readings waiting, dropping one from {device}");
+            }
+        }
+        Err(err) => eprintln!("error: invalid reading on {}: {err}", message.topic),
+    }
+}
+
+/// Returns the level of `topic` matching the `+` of `filter`, if the topic
+/// matches the filter.
+fn device_id<'a>(filter: &str, topic: &'a str) -> Option<&'a str> {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+    let mut device = None;
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("+"), Some(level)) => device = Some(level),
+            (Some(expected), Some(level)) if expected == level => {}
+            (None, None) => return device,
+            _ => return None,
+        }
+    }
+}
+
+fn publish_discovery(client: &AsyncClient, configs: &[(String, serde_json::Value)]) {
+    for (topic, payload) in configs {
+        publish(client, topic, payload.to_string());
+    }
+}
+
+/// Returns the Home Assistant discovery topics and configs of the
+/// published sensors.
+fn discovery_configs(
+    config: &MqttConfig,
+    sensors: &[SensorConfig],
+) -> Vec<(String, serde_json::Value)> {
+    let device = json!({
+        "identifiers": [config.client_id],
+        "name": "Pi home dashboard",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+
+    let indoor = table_sensors(sensors).map(|sensor| DiscoveredSensor {
+        key: &sensor.name,
+        name: sensor.label(),
+        unit: &sensor.unit,
+        device_class: device_class(sensor.chart()),
+        outdoor: false,
+    });
+    let outdoor = OUTDOOR_SENSORS
+        .into_iter()
+        .filter(|_| config.weather_interval_secs > 0);
+
+    indoor
+        .chain(outdoor)
+        .map(|sensor| {
+            let state_topic = if sensor.outdoor {
+                &config.weather_topic
+            } else {
+                &config.sensors_topic
+            };
+            let topic = format!(
+                "{}/sensor/{}/{}/config",
+                config.discovery_prefix, config.client_id, sensor.key
+            );
+            let mut payload = json!({
+                "name": sensor.name,
+                "unique_id": format!("{}_{}", config.client_id, sensor.key),
+                "state_topic": state_topic,
+                "value_template": format!("{{{{ value_json.{} }}}}", sensor.key),
+                "state_class": "measurement",
+                "availability_topic": config.availability_topic,
+                "device": device,
+            });
+            if !sensor.unit.is_empty() {
+                payload["unit_of_measurement"] = sensor.unit.into();
+            }
+            if let Some(device_class) = sensor.device_class {
+                payload["device_class"] = device_class.into();
+            }
+            (topic, payload)
+        })
+        .collect()
+}
+
+/// Home Assistant device class of the measurements drawn in `chart`.
+fn device_class(chart: &str) -> Option<&'static str> {
+    match chart {
+        "temperature" => Some("temperature"),
+        "humidity" => Some("humidity"),
+        "pressure" => Some("atmospheric_pressure"),
+        "co2" => Some("carbon_dioxide"),
+        _ => None,
+    }
+}
+
+async fn publish_sensors(client: AsyncClient, mut feed: Receiver<Arc<SensorData>>, topic: String) {
+    loop {
+        match feed.recv().await {
+            Ok(row) => {
+                let payload = serde_json::to_vec(&*row).unwrap();
+                // Waits while the queue is full, the feed then skips rows.
+                if let Err(err) = client
+                    .publish(&topic, QoS::AtLeastOnce, true, payload)
+                    .await
+                {
+                    eprintln!("error: cannot publish to {topic}: {err}");
+                }
+            }
+            Err(RecvError::Lagged(missed)) => {
+                eprintln!("error: MQTT publisher skipped {missed} sensor rows");
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+async fn publish_weather(client: AsyncClient, weather: Arc<WeatherCache>, config: MqttConfig) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.weather_interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticker.tick().await;
+        let reading = match weather.get().await {
+            Ok(reading) if !reading.stale => reading,
+            Ok(_) => continue,
+            Err(err) => {
+                eprintln!("error: cannot publish weather: {err}");
+                continue;
+            }
+        };
+        let payload = serde_json::to_vec(&reading.report.current).unwrap();
+        if let Err(err) = client
+            .publish(&config.weather_topic, QoS::AtLeastOnce, true, payload)
+            .await
+        {
+            eprintln!("error: cannot publish to {}: {err}", config.weather_topic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn discovery_skips_outdoor_sensors_without_weather() {
+        let config = MqttConfig {
+            weather_interval_secs: 0,
+            ..MqttConfig::default()
+        };
+
+        let configs = discovery_configs(&config, &Config::default().sensors);
+
+        assert_eq!(configs.len(), 4);
+        let (topic, payload) = &configs[3];
+        assert_eq!(
+            topic,
+            "homeassistant/sensor/pi-home-dashboard/htu21d_humidity/config"
+        );
+        assert_eq!(payload["state_topic"], "pi-home-dashboard/sensors");
+        assert_eq!(
+            payload["value_template"],
+            "{{ value_json.htu21d_humidity }}"
+        );
+        assert_eq!(payload["availability_topic"], "pi-home-dashboard/status");
+        assert_eq!(payload["device_class"], "humidity");
+    }
+
+    #[test]
+    fn device_id_is_the_wildcard_level() {
+        let filter = "home/+/readings";
+
+        assert_eq!(device_id(filter, "home/kitchen/readings"), Some("kitchen"));
+        assert_eq!(device_id(filter, "home/kitchen/status"), None);
+        assert_eq!(device_id(filter, "home/kitchen/readings/extra"), None);
+        assert_eq!(device_id(filter, "home/readings"), None);
+    }
+
+    /// Run with `cargo test -- --ignored` and e.g. `mosquitto -p 1883`.
+    #[tokio::test]
+    #[ignore = "needs an MQTT broker on localhost:1883"]
+    async fn publishes_to_local_broker() {
+        let config = MqttConfig {
+            enabled: true,
+            client_id: "pi-home-dashboard-test".to_string(),
+            sensors_topic: "pi-home-dashboard-test/sensors".to_string(),
+            availability_topic: "pi-home-dashboard-test/status".to_string(),
+            discovery_prefix: "pi-home-dashboard-test/homeassistant".to_string(),
+            weather_interval_secs: 0,
+            ..MqttConfig::default()
+        };
+        let (feed, _) = broadcast::channel(16);
+        // Never queried, the weather isn't published.
+        let weather = Arc::new(WeatherCache::new(
+            crate::weather::build_provider(&Default::default(), &reqwest::Client::new()),
+            Duration::from_secs(60),
+        ));
+
+        let (subscriber, mut eventloop) = AsyncClient::new(
+            MqttOptions::new("pi-home-dashboard-test-sub", "localhost", 1883),
+            16,
+        );
+        subscriber
+            .subscribe("pi-home-dashboard-test/#", QoS::AtLeastOnce)
+            .await
+            .unwrap();
+
+        spawn(&config, &Config::default().sensors, &feed, weather, None);
+        let mut sent = false;
+        let mut received = Vec::new();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !received
+                .iter()
+                .any(|(topic, _)| *topic == config.sensors_topic)
+            {
+                if let Event::Incoming(Packet::Publish(message)) = eventloop.poll().await.unwrap() {
+                    if !sent && message.topic == config.availability_topic {
+                        let row = Arc::new(SensorData {
+                            timestamp: "2026-10-16 10:00:00".to_string(),
+                            values: [("bmp280_temp".to_string(), Some(20.5))].into(),
+                        });
+                        assert!(feed.send(row).is_ok());
+                        sent = true;
+                    }
+                    received.push((message.topic, message.payload));
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(received.iter().any(
+            |(topic, payload)| *topic == config.availability_topic && &payload[..] == b"online"
+        ));
+        assert!(received
+            .iter()
+            .any(|(topic, _)| topic.ends_with("/bmp280_temp/config")));
+        let (_, row) = received
+            .iter()
+            .find(|(topic, _)| *topic == config.sensors_topic)
+            .unwrap();
+        let row: serde_json::Value = serde_json::from_slice(row).unwrap();
+        assert_eq!(row["bmp280_temp"], 20.5);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{Notification, Notifier, NotifyFuture};
+use crate::{
+    config::{NotificationChannel, SmtpSecurity},
+    error::AppError,
+};
+
+/// Timeout of each SMTP command.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends the notifications by email through an SMTP relay.
+pub struct Email {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl Email {
+    /// Builds the transport of a channel that went through
+    /// [`NotificationChannel::validate`](crate::config).
+    pub fn new(channel: &NotificationChannel) -> Result<Self, String> {
+        let host = channel.smtp_host.as_deref().unwrap_or_default();
+        let mut builder = match channel.smtp_security {
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|err| format!("invalid SMTP host {host}: {err}"))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|err| format!("invalid SMTP host {host}: {err}"))?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = channel.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&channel.smtp_username, &channel.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let parse = |address: &str| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|err| format!("invalid address {address:?}: {err}"))
+        };
+        Ok(Self {
+            transport: builder.timeout(Some(SMTP_TIMEOUT)).build(),
+            from: parse(channel.from.as_deref().unwrap_or_default())?,
+            to: channel
+                .to
+                .iter()
+                .map(|to| parse(to))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Notifier for Email {
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let mut message = Message::builder().from(self.from.clone());
+            for to in &self.to {
+                message = message.to(to.clone());
+            }
+            let message = message
+                .subject(&notification.title)
+                .header(ContentType::TEXT_PLAIN)
+                .body(notification.body.clone())
+                .map_err(|err| AppError::Internal(format!("cannot build email: {err}")))?;
+
+            self.transport
+                .send(message)
+                .await
+                .map_err(|err| AppError::Upstream(format!("SMTP delivery failed: {err}")))?;
+            Ok(())
+        })
+    }
+}
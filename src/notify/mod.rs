@@ -0,0 +1,353 @@
+mod email;
+mod push;
+pub mod template;
+mod webhook;
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use rusqlite::{params, Connection};
+use tokio::sync::mpsc;
+
+use crate::{
+    alerts::AlertEvent,
+    config::{Config, NotificationChannel, NotifierKind},
+    data::{db_now, DB_TIMESTAMP_FORMAT},
+    db::{self, DbPool},
+    error::AppError,
+};
+
+/// Number of events waiting for a channel before new ones are dropped.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Alert event with its rendered title and body.
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub event: AlertEvent,
+}
+
+pub type NotifyFuture<'a> = Pin<Box<dyn Future<Output = Result<(), AppError>> + Send + 'a>>;
+
+/// Backend delivering the notifications of one channel.
+pub trait Notifier: Send + Sync {
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a>;
+}
+
+/// Queues of the notification channels.
+///
+/// Each channel is served by its own task, so that a channel retrying a
+/// delivery doesn't delay the others, while its notifications are still
+/// sent in order.
+#[derive(Clone)]
+pub struct Notifications {
+    queues: Vec<(String, mpsc::Sender<AlertEvent>)>,
+}
+
+impl Notifications {
+    pub fn send(&self, event: &AlertEvent) {
+        for (name, queue) in &self.queues {
+            if queue.try_send(event.clone()).is_err() {
+                eprintln!(
+                    "error: notification queue of {name} is full, dropping alert {}",
+                    event.alert.id
+                );
+            }
+        }
+    }
+}
+
+/// Builds the channels listed in the configuration and starts their tasks,
+/// which record every delivery in the `NotificationLog` table.
+pub fn spawn_dispatchers(
+    config: Arc<Config>,
+    writer: DbPool,
+    client: &reqwest::Client,
+) -> Result<Notifications, String> {
+    let mut queues = Vec::new();
+    for channel in &config.notifications.channels {
+        let notifier = build_notifier(channel, client)
+            .map_err(|err| format!("notification channel {:?}: {err}", channel.name))?;
+        let (tx, mut rx) = mpsc::channel::<AlertEvent>(QUEUE_CAPACITY);
+        queues.push((channel.name.clone(), tx));
+
+        let channel = channel.clone();
+        let config = config.clone();
+        let writer = writer.clone();
+        tokio::spawn(async move {
+            let retries = config.notifications.retries;
+            let retry_delay = Duration::from_secs(config.notifications.retry_delay_secs);
+            while let Some(event) = rx.recv().await {
+                let notification = Notification {
+                    title: template::render(&channel.title, &event),
+                    body: template::render(&channel.body, &event),
+                    event,
+                };
+                let (attempts, result) =
+                    deliver(notifier.as_ref(), &notification, retries, retry_delay).await;
+                if let Err(err) = &result {
+                    eprintln!(
+                        "error: cannot notify {} of alert {}: {err}",
+                        channel.name, notification.event.alert.id
+                    );
+                }
+
+                let name = channel.name.clone();
+                let timestamp = db_now(&config.database)
+                    .format(DB_TIMESTAMP_FORMAT)
+                    .to_string();
+                let error = result.err().map(|err| err.to_string());
+                if let Err(err) = db::run(&writer, move |conn| {
+                    log_delivery(
+                        conn,
+                        &name,
+                        &notification.event,
+                        attempts,
+                        error.as_deref(),
+                        &timestamp,
+                    )
+                })
+                .await
+                {
+                    eprintln!("error: cannot log notification: {err}");
+                }
+            }
+        });
+    }
+    Ok(Notifications { queues })
+}
+
+fn build_notifier(
+    channel: &NotificationChannel,
+    client: &reqwest::Client,
+) -> Result<Box<dyn Notifier>, String> {
+    let url = channel.url.clone().unwrap_or_default();
+    Ok(match channel.kind {
+        NotifierKind::Webhook => Box::new(webhook::Webhook::new(client.clone(), url)),
+        NotifierKind::Ntfy => Box::new(push::Ntfy::new(client.clone(), url, channel.token.clone())),
+        NotifierKind::Gotify => Box::new(push::Gotify::new(
+            client.clone(),
+            &url,
+            channel.token.clone().unwrap_or_default(),
+        )),
+        NotifierKind::Email => Box::new(email::Email::new(channel)?),
+    })
+}
+
+/// Sends a notification, retrying up to `retries` times with an exponential
+/// backoff. Returns the number of attempts made.
+async fn deliver(
+    notifier: &dyn Notifier,
+    notification: &Notification,
+    retries: u32,
+    retry_delay: Duration,
+) -> (u32, Result<(), AppError>) {
+    let mut delay = retry_delay;
+    let mut attempt = 1;
+    loop {
+        match notifier.send(notification).await {
+            Err(err) if attempt <= retries => {
+                eprintln!("error: notification attempt {attempt} failed: {err}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return (attempt, result),
+        }
+    }
+}
+
+fn log_delivery(
+    conn: &Connection,
+    channel: &str,
+    event: &AlertEvent,
+    attempts: u32,
+    error: Option<&str>,
+    timestamp: &str,
+) -> rusqlite::Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO NotificationLog \
+         (alert_id, event, channel, attempts, delivered, error, timestamp) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?
+    .execute(params![
+        event.alert.id,
+        event.state.as_str(),
+        channel,
+        attempts,
+        error.is_none(),
+        error,
+        timestamp
+    ])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use axum::{http::StatusCode, routing::post, Json, Router};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{
+        alerts::{Alert, AlertState},
+        config::SmtpSecurity,
+    };
+
+    fn notification() -> Notification {
+        let event = AlertEvent {
+            state: AlertState::Raised,
+            alert: Alert {
+                id: 7,
+                rule: "basement-humidity".to_string(),
+                message: "htu21d_humidity is 72.0, above 70".to_string(),
+                value: 72.0,
+                triggered_at: "2026-10-16 10:00:00".to_string(),
+                resolved_at: None,
+                acknowledged_at: None,
+            },
+        };
+        Notification {
+            title: template::render("[{state}] {rule}", &event),
+            body: template::render("{message}", &event),
+            event,
+        }
+    }
+
+    /// Local webhook receiver failing the first `failures` requests and
+    /// keeping the last body it accepted.
+    async fn start_webhook(
+        failures: usize,
+    ) -> (
+        String,
+        Arc<AtomicUsize>,
+        Arc<Mutex<Option<serde_json::Value>>>,
+    ) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(None));
+
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let hits = hits.clone();
+                let received = received.clone();
+                move |Json(body): Json<serde_json::Value>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                        return StatusCode::INTERNAL_SERVER_ERROR;
+                    }
+                    *received.lock().unwrap() = Some(body);
+                    StatusCode::NO_CONTENT
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{addr}/hook"), hits, received)
+    }
+
+    /// Minimal SMTP server accepting one message and returning its data.
+    async fn start_smtp_sink() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut data = String::new();
+            let mut in_data = false;
+
+            writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let reply: &[u8] = if in_data {
+                    if line == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                        continue;
+                    }
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            data
+        });
+
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn webhook_is_retried_until_delivered() {
+        let (url, hits, received) = start_webhook(2).await;
+        let webhook = webhook::Webhook::new(reqwest::Client::new(), url);
+
+        let (attempts, result) =
+            deliver(&webhook, &notification(), 3, Duration::from_millis(1)).await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        let body = received.lock().unwrap().take().unwrap();
+        assert_eq!(body["title"], "[raised] basement-humidity");
+        assert_eq!(body["state"], "raised");
+        assert_eq!(body["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn delivery_gives_up_after_the_retries() {
+        let (url, hits, _) = start_webhook(usize::MAX).await;
+        let webhook = webhook::Webhook::new(reqwest::Client::new(), url);
+
+        let (attempts, result) =
+            deliver(&webhook, &notification(), 2, Duration::from_millis(1)).await;
+
+        assert!(matches!(result, Err(AppError::Upstream(_))));
+        assert_eq!(attempts, 3);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn email_is_sent_through_smtp() {
+        let (port, sink) = start_smtp_sink().await;
+        let email = email::Email::new(&NotificationChannel {
+            name: "mail".to_string(),
+            kind: NotifierKind::Email,
+            url: None,
+            token: None,
+            title: String::new(),
+            body: String::new(),
+            smtp_host: Some("127.0.0.1".to_string()),
+            smtp_port: Some(port),
+            smtp_security: SmtpSecurity::None,
+            smtp_username: None,
+            smtp_password: None,
+            from: Some("Dashboard <pi@example.com>".to_string()),
+            to: vec!["home@example.com".to_string()],
+        })
+        .unwrap();
+
+        let (_, result) = deliver(&email, &notification(), 0, Duration::ZERO).await;
+
+        assert!(result.is_ok());
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: [raised] basement-humidity"));
+        assert!(data.contains("htu21d_humidity is 72.0, above 70"));
+    }
+}
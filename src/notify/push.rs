@@ -0,0 +1,91 @@
+use serde_json::json;
+
+use super::{Notification, Notifier, NotifyFuture};
+use crate::{alerts::AlertState, error::AppError};
+
+/// Publishes the notifications to an ntfy topic.
+pub struct Ntfy {
+    client: reqwest::Client,
+    /// URL of the topic, e.g. `https://ntfy.sh/my-topic`.
+    url: String,
+    token: Option<String>,
+}
+
+/// Sends the notifications to a Gotify server.
+pub struct Gotify {
+    client: reqwest::Client,
+    /// URL of the `/message` endpoint.
+    url: String,
+    token: String,
+}
+
+impl Ntfy {
+    pub fn new(client: reqwest::Client, url: String, token: Option<String>) -> Self {
+        Self { client, url, token }
+    }
+}
+
+impl Notifier for Ntfy {
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let (priority, tags) = match notification.event.state {
+                AlertState::Raised => ("high", "warning"),
+                AlertState::Resolved => ("default", "white_check_mark"),
+            };
+            // The title goes in the query rather than in a header, which
+            // couldn't hold non-ASCII characters.
+            let mut request = self
+                .client
+                .post(&self.url)
+                .query(&[
+                    ("title", notification.title.as_str()),
+                    ("priority", priority),
+                    ("tags", tags),
+                ])
+                .body(notification.body.clone());
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            request
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| AppError::Upstream(format!("ntfy request failed: {err}")))?;
+            Ok(())
+        })
+    }
+}
+
+impl Gotify {
+    pub fn new(client: reqwest::Client, server: &str, token: String) -> Self {
+        Self {
+            client,
+            url: format!("{}/message", server.trim_end_matches('/')),
+            token,
+        }
+    }
+}
+
+impl Notifier for Gotify {
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let priority = match notification.event.state {
+                AlertState::Raised => 8,
+                AlertState::Resolved => 4,
+            };
+            self.client
+                .post(&self.url)
+                .header("X-Gotify-Key", &self.token)
+                .json(&json!({
+                    "title": notification.title,
+                    "message": notification.body,
+                    "priority": priority,
+                }))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| AppError::Upstream(format!("Gotify request failed: {err}")))?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,104 @@
+//! Templates of the notification titles and bodies.
+//!
+//! A template is plain text where `{name}` is replaced by a field of the
+//! alert: `rule`, `state` (`raised` or `resolved`), `message`, `value`,
+//! `triggered_at` and `resolved_at` (empty while the alert is active).
+//! Literal braces are written `{{` and `}}`.
+
+use crate::alerts::AlertEvent;
+
+const PLACEHOLDERS: [&str; 6] = [
+    "rule",
+    "state",
+    "message",
+    "value",
+    "triggered_at",
+    "resolved_at",
+];
+
+/// Checks that `template` is well-formed and only uses known placeholders.
+pub fn check(template: &str) -> Result<(), String> {
+    render_with(template, |name| {
+        PLACEHOLDERS.contains(&name).then(String::new)
+    })
+    .map(|_| ())
+}
+
+/// Renders a template that went through [`check`].
+pub fn render(template: &str, event: &AlertEvent) -> String {
+    let alert = &event.alert;
+    render_with(template, |name| {
+        Some(match name {
+            "rule" => alert.rule.clone(),
+            "state" => event.state.as_str().to_string(),
+            "message" => alert.message.clone(),
+            "value" => format!("{:.1}", alert.value),
+            "triggered_at" => alert.triggered_at.clone(),
+            "resolved_at" => alert.resolved_at.clone().unwrap_or_default(),
+            _ => return None,
+        })
+    })
+    .unwrap_or_else(|_| template.to_string())
+}
+
+fn render_with(template: &str, field: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+
+        if let Some(after) = rest.strip_prefix(brace) {
+            out.push_str(brace);
+            rest = after;
+        } else if brace == "}" {
+            return Err("unmatched `}`, write `}}` for a literal brace".to_string());
+        } else {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| "unclosed `{`, write `{{` for a literal brace".to_string())?;
+            let name = &rest[..end];
+            let value = field(name).ok_or_else(|| format!("unknown placeholder {{{name}}}"))?;
+            out.push_str(&value);
+            rest = &rest[end + 1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::{Alert, AlertState};
+
+    #[test]
+    fn placeholders_and_escapes_are_rendered() {
+        let event = AlertEvent {
+            state: AlertState::Raised,
+            alert: Alert {
+                id: 1,
+                rule: "basement-humidity".to_string(),
+                message: "htu21d_humidity is 72.0, above 70".to_string(),
+                value: 72.0,
+                triggered_at: "2026-10-16 10:00:00".to_string(),
+                resolved_at: None,
+                acknowledged_at: None,
+            },
+        };
+
+        assert_eq!(
+            render("{{{state}}} {rule}: {value} {resolved_at}", &event),
+            "{raised} basement-humidity: 72.0 "
+        );
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        assert!(check("[{state}] {rule}").is_ok());
+        assert!(check("{unknown}").is_err());
+        assert!(check("{rule").is_err());
+        assert!(check("rule}").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use serde::Serialize;
+
+use super::{Notification, Notifier, NotifyFuture};
+use crate::{
+    alerts::{Alert, AlertState},
+    error::AppError,
+};
+
+/// Posts the notifications as JSON to a URL.
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// Body of the webhook requests: the alert fields with the rendered title
+/// and body.
+#[derive(Serialize)]
+struct Payload<'a> {
+    title: &'a str,
+    body: &'a str,
+    state: AlertState,
+    #[serde(flatten)]
+    alert: &'a Alert,
+}
+
+impl Webhook {
+    pub fn new(client: reqwest::Client, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+impl Notifier for Webhook {
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            self.client
+                .post(&self.url)
+                .json(&Payload {
+                    title: &notification.title,
+                    body: &notification.body,
+                    state: notification.event.state,
+                    alert: &notification.event.alert,
+                })
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| AppError::Upstream(format!("webhook request failed: {err}")))?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use crate::{
+    config::Config, db::DbPool, metrics::Metrics, stream::SensorFeed, templates::Pages,
+    weather::WeatherCache,
+};
+
+/// State shared by all the request handlers.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    /// Read-only connections to the sensor database.
+    pub db: DbPool,
+    /// Read-write connection for the tables owned by the dashboard.
+    pub writer: DbPool,
+    pub sensor_feed: SensorFeed,
+    pub weather: Arc<WeatherCache>,
+    pub metrics: Arc<Metrics>,
+    pub pages: Arc<Pages>,
+}
@@ -0,0 +1,178 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use tokio::{sync::broadcast, time::MissedTickBehavior};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+use crate::{
+    config::SensorConfig,
+    data::{query_data_after, SensorData},
+    db::{self, DbPool},
+    state::AppState,
+};
+
+/// Number of rows buffered for a slow client before it misses some.
+const FEED_CAPACITY: usize = 256;
+/// Maximum number of rows read from the database at each poll.
+const POLL_BATCH: u32 = 1000;
+
+/// Broadcasts the rows inserted in `SensorData` to the `/data/stream`
+/// clients.
+pub type SensorFeed = broadcast::Sender<Arc<SensorData>>;
+
+/// Creates the feed and starts the task polling the database for new rows
+/// every `interval`.
+///
+/// The database is only polled while clients are connected. The first poll
+/// after a quiet period only records the newest row, so that clients
+/// receive the rows inserted after they connected.
+pub fn spawn_feed(db: DbPool, sensors: Vec<SensorConfig>, interval: Duration) -> SensorFeed {
+    let sensors: Arc<[SensorConfig]> = sensors.into();
+    let (feed, _) = broadcast::channel(FEED_CAPACITY);
+
+    tokio::spawn({
+        let feed = feed.clone();
+        async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            // `(timestamp, rowid)` of the last row sent.
+            let mut last: Option<(String, i64)> = None;
+            loop {
+                ticker.tick().await;
+                if feed.receiver_count() == 0 {
+                    last = None;
+                    continue;
+                }
+
+                let catching_up = last.is_none();
+                let after = last.clone();
+                let sensors = sensors.clone();
+                let rows = match db::run(&db, move |conn| {
+                    let after = after
+                        .as_ref()
+                        .map(|(timestamp, rowid)| (timestamp.as_str(), *rowid));
+                    query_data_after(conn, &sensors, after, POLL_BATCH)
+                })
+                .await
+                {
+                    Ok(rows) => rows,
+                    Err(err) => {
+                        eprintln!("error: cannot poll new sensor data: {err}");
+                        continue;
+                    }
+                };
+
+                if let Some((rowid, row)) = rows.last() {
+                    last = Some((row.timestamp.clone(), *rowid));
+                }
+                if !catching_up {
+                    for (_, row) in rows {
+                        // Fails only when every client disconnected meanwhile.
+                        let _ = feed.send(Arc::new(row));
+                    }
+                }
+            }
+        }
+    });
+
+    feed
+}
+
+/// Server-Sent Events stream of the new sensor rows.
+///
+/// Each row is sent as a `reading` event holding the JSON of a
+/// [`SensorData`]. A `lagged` event tells the client that it missed rows and
+/// should refetch `/data`.
+pub async fn stream_data(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rows = BroadcastStream::new(state.sensor_feed.subscribe()).map(|row| {
+        let event = match row {
+            Ok(row) => Event::default()
+                .event("reading")
+                .id(row.timestamp.clone())
+                .json_data(&*row)
+                .unwrap_or_else(|_| Event::default().event("lagged")),
+            Err(BroadcastStreamRecvError::Lagged(_)) => Event::default().event("lagged"),
+        };
+        Ok(event)
+    });
+
+    Sse::new(rows).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn rows_sharing_the_last_timestamp_are_sent() {
+        let path = std::env::temp_dir().join(format!(
+            "pi-home-dashboard-stream-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let pool = r2d2::Pool::builder()
+            .max_size(2)
+            .build(SqliteConnectionManager::file(&path))
+            .unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE SensorData (
+                     timestamp TEXT,
+                     bmp280_temperature REAL,
+                     bmp280_pressure REAL,
+                     htu21d_temperature REAL,
+                     htu21d_humidity REAL
+                 );
+                 INSERT INTO SensorData VALUES ('2026-10-16 10:01:00', 20, 1012, 20.5, 55);",
+            )
+            .unwrap();
+        let insert = |temp: f64| {
+            pool.get()
+                .unwrap()
+                .execute(
+                    "INSERT INTO SensorData VALUES ('2026-10-16 10:02:00', ?1, 1012, 20.5, 55)",
+                    [temp],
+                )
+                .unwrap();
+        };
+
+        let feed = spawn_feed(
+            pool.clone(),
+            Config::default().sensors,
+            Duration::from_millis(10),
+        );
+        let mut rows = feed.subscribe();
+        // Leaves time for the first poll, which only records the newest row.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut received = Vec::new();
+        for temp in [21.0, 22.0] {
+            insert(temp);
+            let row = tokio::time::timeout(Duration::from_secs(2), rows.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            received.push((row.timestamp.clone(), row.value("bmp280_temp")));
+        }
+        assert_eq!(
+            received,
+            [
+                ("2026-10-16 10:02:00".to_string(), Some(21.0)),
+                ("2026-10-16 10:02:00".to_string(), Some(22.0)),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::{
+    assets::{self, Asset},
+    config::{Config, PressureUnit, TemperatureUnit, WindSpeedUnit},
+};
+
+/// Files of the `templates` folder, the pages extending `base.html`.
+const TEMPLATES: [&str; 3] = ["base.html", "index.html", "login.html"];
+/// Templates served as pages.
+const PAGES: [&str; 2] = ["index.html", "login.html"];
+
+/// Values the templates are rendered with, built from the configuration.
+#[derive(Serialize)]
+struct Context<'a> {
+    title: &'a str,
+    location: Option<&'a str>,
+    units: Units,
+    /// Measurements shown on the dashboard, empty for all of them.
+    sensors: Vec<SensorContext<'a>>,
+    refresh_secs: u64,
+}
+
+/// Symbols of the units the values are shown in.
+#[derive(Serialize)]
+struct Units {
+    temperature: &'static str,
+    wind_speed: &'static str,
+    pressure: &'static str,
+}
+
+#[derive(Serialize)]
+struct SensorContext<'a> {
+    name: &'a str,
+    label: &'a str,
+    unit: &'a str,
+    chart: &'a str,
+}
+
+impl<'a> Context<'a> {
+    fn new(config: &'a Config) -> Self {
+        let dashboard = &config.dashboard;
+        Self {
+            title: &dashboard.title,
+            location: dashboard.location.as_deref(),
+            units: Units {
+                temperature: dashboard.temperature_unit.symbol(),
+                wind_speed: dashboard.wind_speed_unit.symbol(),
+                pressure: dashboard.pressure_unit.symbol(),
+            },
+            sensors: dashboard
+                .sensors
+                .iter()
+                .filter_map(|name| config.sensors.iter().find(|sensor| &sensor.name == name))
+                .map(|sensor| SensorContext {
+                    name: &sensor.name,
+                    label: sensor.label(),
+                    unit: &sensor.unit,
+                    chart: sensor.chart(),
+                })
+                .collect(),
+            refresh_secs: dashboard.refresh_secs,
+        }
+    }
+}
+
+impl TemperatureUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+}
+
+impl WindSpeedUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            WindSpeedUnit::KilometersPerHour => "km/h",
+            WindSpeedUnit::MetersPerSecond => "m/s",
+            WindSpeedUnit::MilesPerHour => "mph",
+            WindSpeedUnit::Knots => "kn",
+        }
+    }
+}
+
+impl PressureUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PressureUnit::Hectopascals => "hPa",
+            PressureUnit::InchesOfMercury => "inHg",
+            PressureUnit::MillimetersOfMercury => "mmHg",
+        }
+    }
+}
+
+/// Pages rendered once, the context only depending on the configuration.
+pub struct Pages(HashMap<&'static str, Asset>);
+
+impl Pages {
+    /// Renders all the pages, so that a broken template stops the server at
+    /// startup rather than failing its requests.
+    pub async fn render(config: &Config) -> Result<Self, String> {
+        let env = environment(config).await?;
+        let context = Context::new(config);
+        let mut pages = HashMap::new();
+        for name in PAGES {
+            pages.insert(name, render(&env, name, &context)?);
+        }
+        Ok(Self(pages))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Asset> {
+        self.0.get(name).cloned()
+    }
+}
+
+/// Renders a page from the current templates.
+pub async fn render_page(config: &Config, name: &str) -> Result<Asset, String> {
+    let env = environment(config).await?;
+    render(&env, name, &Context::new(config))
+}
+
+/// Loads the templates from `assets.dir` or the binary. Variables missing
+/// from the context are errors rather than empty strings, so that typos
+/// are caught when rendering.
+async fn environment(config: &Config) -> Result<Environment<'static>, String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    for name in TEMPLATES {
+        let asset = assets::load(&config.assets, &format!("templates/{name}"))
+            .await
+            .map_err(|err| err.to_string())?
+            .ok_or_else(|| format!("missing template {name}"))?;
+        let source = String::from_utf8(asset.data.into_owned())
+            .map_err(|_| format!("template {name} is not valid UTF-8"))?;
+        env.add_template_owned(name, source)
+            .map_err(|err| format!("invalid template {name}: {err}"))?;
+    }
+    Ok(env)
+}
+
+fn render(env: &Environment, name: &str, context: &Context) -> Result<Asset, String> {
+    env.get_template(name)
+        .and_then(|template| template.render(context))
+        .map(|html| Asset::new(html.into_bytes()))
+        .map_err(|err| format!("cannot render template {name}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AssetsConfig;
+
+    use super::*;
+
+    fn html(asset: &Asset) -> &str {
+        std::str::from_utf8(&asset.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn pages_are_rendered_from_the_config() {
+        let mut config = Config::default();
+        config.dashboard.title = "Chalet <Alps>".to_string();
+        config.dashboard.location = Some("Chamonix".to_string());
+        config.dashboard.temperature_unit = TemperatureUnit::Fahrenheit;
+        config.dashboard.sensors = vec!["co2".to_string()];
+        config.dashboard.refresh_secs = 120;
+
+        let pages = Pages::render(&config).await.unwrap();
+        let index = pages.get("index.html").unwrap();
+        let index = html(&index);
+        assert!(index.contains("<title>Chalet &lt;Alps&gt;</title>"));
+        assert!(index.contains("Weather in Chamonix"));
+        assert!(index.contains(r#""temperature":"°F""#));
+        assert!(index.contains(r#"const SENSORS = ["co2"];"#));
+        assert!(index.contains("120 * 1000"));
+        assert!(html(&pages.get("login.html").unwrap()).contains("Chalet &lt;Alps&gt;"));
+        assert!(pages.get("base.html").is_none());
+    }
+
+    #[tokio::test]
+    async fn template_errors_are_reported() {
+        let dir = std::env::temp_dir().join(format!(
+            "pi-home-dashboard-templates-{}",
+            std::process::id()
+        ));
+        let templates = dir.join("templates");
+        std::fs::create_dir_all(&templates).unwrap();
+        let source = env!("CARGO_MANIFEST_DIR");
+        for name in TEMPLATES {
+            std::fs::copy(
+                format!("{source}/assets/templates/{name}"),
+                templates.join(name),
+            )
+            .unwrap();
+        }
+        let config = Config {
+            assets: AssetsConfig {
+                dir: Some(dir.clone()),
+            },
+            ..Config::default()
+        };
+        assert!(Pages::render(&config).await.is_ok());
+
+        std::fs::write(templates.join("login.html"), "{{ site_name }}").unwrap();
+        let err = Pages::render(&config).await.err().unwrap();
+        assert!(err.contains("login.html"), "{err}");
+        assert!(err.contains("undefined"), "{err}");
+
+        std::fs::write(templates.join("login.html"), "{% if title %}").unwrap();
+        let err = Pages::render(&config).await.err().unwrap();
+        assert!(err.starts_with("invalid template login.html"), "{err}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::{io::Write, net::SocketAddr, os::unix::fs::OpenOptionsExt, path::Path};
+
+use axum::{
+    http::{header, HeaderMap, Uri},
+    response::Redirect,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use rcgen::{CertificateParams, DnType, KeyPair};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::TlsConfig;
+
+/// Loads the certificate and key, after generating a self-signed pair if
+/// neither file exists yet.
+pub async fn load(config: &TlsConfig) -> Result<RustlsConfig, String> {
+    match (config.cert_path.exists(), config.key_path.exists()) {
+        (true, true) => {}
+        (false, false) => generate_self_signed(config)?,
+        (true, false) => {
+            return Err(format!(
+                "TLS certificate {} has no key at {}",
+                config.cert_path.display(),
+                config.key_path.display()
+            ))
+        }
+        (false, true) => {
+            return Err(format!(
+                "TLS key {} has no certificate at {}",
+                config.key_path.display(),
+                config.cert_path.display()
+            ))
+        }
+    }
+    RustlsConfig::from_pem_file(&config.cert_path, &config.key_path)
+        .await
+        .map_err(|err| {
+            format!(
+                "cannot load TLS certificate {}: {err}",
+                config.cert_path.display()
+            )
+        })
+}
+
+/// Writes a self-signed certificate for `self_signed_names`, which browsers
+/// accept once the exception is confirmed.
+fn generate_self_signed(config: &TlsConfig) -> Result<(), String> {
+    let names = if config.self_signed_names.is_empty() {
+        default_names()
+    } else {
+        config.self_signed_names.clone()
+    };
+    let mut params = CertificateParams::new(names)
+        .map_err(|err| format!("invalid tls.self_signed_names: {err}"))?;
+    params
+        .distinguished_name
+        .push(DnType::CommonName, env!("CARGO_PKG_NAME"));
+    let key = KeyPair::generate().map_err(|err| format!("cannot generate TLS key: {err}"))?;
+    let cert = params
+        .self_signed(&key)
+        .map_err(|err| format!("cannot generate TLS certificate: {err}"))?;
+
+    write_pem(&config.key_path, &key.serialize_pem(), 0o600)?;
+    write_pem(&config.cert_path, &cert.pem(), 0o644)?;
+    eprintln!(
+        "generated a self-signed TLS certificate in {}",
+        config.cert_path.display()
+    );
+    Ok(())
+}
+
+/// `localhost`, and the host name of the Pi with its mDNS variant.
+fn default_names() -> Vec<String> {
+    let mut names = vec!["localhost".to_string()];
+    if let Ok(hostname) = std::fs::read_to_string("/proc/sys/kernel/hostname") {
+        let hostname = hostname.trim();
+        if !hostname.is_empty() && hostname != "localhost" {
+            names.push(hostname.to_string());
+            names.push(format!("{hostname}.local"));
+        }
+    }
+    names
+}
+
+fn write_pem(path: &Path, pem: &str, mode: u32) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("cannot create {}: {err}", dir.display()))?;
+    }
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)
+        .and_then(|mut file| file.write_all(pem.as_bytes()))
+        .map_err(|err| format!("cannot write {}: {err}", path.display()))
+}
+
+/// Starts the task reloading the certificate and key on `SIGHUP`, e.g. after
+/// a renewal. The previous ones are kept if the new ones are invalid.
+pub fn spawn_reloader(tls: RustlsConfig, config: TlsConfig) -> std::io::Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if let Err(err) = tls
+                .reload_from_pem_file(&config.cert_path, &config.key_path)
+                .await
+            {
+                eprintln!(
+                    "error: cannot reload TLS certificate {}: {err}",
+                    config.cert_path.display()
+                );
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Starts a plain HTTP server on `bind` redirecting every request to the
+/// HTTPS server listening on `https_port`.
+pub async fn spawn_redirect(bind: SocketAddr, https_port: u16) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok());
+        Redirect::permanent(&https_url(host, https_port, &uri))
+    });
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            eprintln!("error: HTTPS redirect server failed: {err}");
+        }
+    });
+    Ok(())
+}
+
+/// URL of the HTTPS version of a request made to `host`.
+fn https_url(host: Option<&str>, https_port: u16, uri: &Uri) -> String {
+    let host = host.unwrap_or("localhost");
+    // Strips the port of the HTTP server, keeping IPv6 addresses whole.
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirects_keep_the_host_and_path() {
+        let uri: Uri = "/data?limit=10".parse().unwrap();
+
+        assert_eq!(
+            https_url(Some("pi.local:8080"), 3000, &uri),
+            "https://pi.local:3000/data?limit=10"
+        );
+        assert_eq!(
+            https_url(Some("pi.local"), 443, &uri),
+            "https://pi.local/data?limit=10"
+        );
+        assert_eq!(
+            https_url(Some("[fe80::1]:80"), 3000, &"/".parse().unwrap()),
+            "https://[fe80::1]:3000/"
+        );
+        assert_eq!(
+            https_url(Some("[fe80::1]"), 443, &uri),
+            "https://[fe80::1]/data?limit=10"
+        );
+    }
+
+    #[tokio::test]
+    async fn self_signed_certificate_is_generated_once() {
+        let dir =
+            std::env::temp_dir().join(format!("pi-home-dashboard-tls-{}", std::process::id()));
+        let config = TlsConfig {
+            enabled: true,
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("private/key.pem"),
+            self_signed_names: vec!["pi.local".to_string(), "192.168.1.20".to_string()],
+            redirect_bind: None,
+        };
+
+        load(&config).await.unwrap();
+        let cert = std::fs::read_to_string(&config.cert_path).unwrap();
+        assert!(cert.starts_with("-----BEGIN CERTIFICATE-----"));
+        let mode = std::fs::metadata(&config.key_path).unwrap().permissions();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&mode) & 0o777,
+            0o600
+        );
+
+        // The existing pair is reused rather than overwritten.
+        load(&config).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&config.cert_path).unwrap(), cert);
+
+        std::fs::remove_file(&config.key_path).unwrap();
+        assert!(load(&config).await.unwrap_err().contains("has no key"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
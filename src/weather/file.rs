@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use crate::error::AppError;
+
+use super::{open_meteo, ProviderFuture, WeatherProvider};
+
+/// Serves a report read from a JSON fixture, in the format of an open-meteo
+/// response. Meant for offline setups and tests.
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl WeatherProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn fetch(&self) -> ProviderFuture<'_> {
+        Box::pin(async move {
+            let body = tokio::fs::read(&self.path).await.map_err(|err| {
+                AppError::Upstream(format!("cannot read {}: {err}", self.path.display()))
+            })?;
+            open_meteo::parse_response(&body).map_err(|err| {
+                AppError::Upstream(format!(
+                    "invalid weather file {}: {err}",
+                    self.path.display()
+                ))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/weather/fixtures")
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn reads_an_open_meteo_response() {
+        let report = FileProvider::new(fixture("open_meteo.json"))
+            .fetch()
+            .await
+            .unwrap();
+
+        assert_eq!(report.current.external_temp, 12.5);
+        assert_eq!(report.current.external_humidity, Some(81.0));
+        assert_eq!(report.hourly.len(), 3);
+        assert_eq!(report.daily[1].description, "Slight rain");
+        assert_eq!(report.daily[2].sunrise.as_deref(), Some("2026-10-18T08:14"));
+    }
+
+    #[tokio::test]
+    async fn missing_or_invalid_files_are_upstream_errors() {
+        let err = FileProvider::new(fixture("missing.json"))
+            .fetch()
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, AppError::Upstream(msg) if msg.starts_with("cannot read") && msg.contains("missing.json")),
+            "{err}"
+        );
+
+        let err = FileProvider::new(fixture("met_norway_complete.json"))
+            .fetch()
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, AppError::Upstream(msg) if msg.starts_with("invalid weather file")),
+            "{err}"
+        );
+    }
+}
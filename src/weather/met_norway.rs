@@ -0,0 +1,320 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Local, Offset, TimeZone, Timelike};
+use serde::Deserialize;
+
+use crate::{config::WeatherConfig, error::AppError};
+
+use super::{
+    weather_description, DailyForecast, HourlyForecast, ProviderFuture, Weather, WeatherProvider,
+    WeatherReport, FORECAST_DAYS, FORECAST_HOURS,
+};
+
+/// [MET Norway](https://api.met.no/weatherapi/locationforecast/2.0/documentation)
+/// locationforecast API.
+///
+/// It doesn't provide sunrise and sunset times, and reports the pressure at
+/// sea level rather than at the surface.
+pub struct MetNorway {
+    client: reqwest::Client,
+    config: WeatherConfig,
+}
+
+impl MetNorway {
+    pub fn new(client: reqwest::Client, config: WeatherConfig) -> Self {
+        Self { client, config }
+    }
+}
+
+impl WeatherProvider for MetNorway {
+    fn name(&self) -> &'static str {
+        "met-norway"
+    }
+
+    fn fetch(&self) -> ProviderFuture<'_> {
+        Box::pin(fetch(&self.client, &self.config))
+    }
+}
+
+#[derive(Deserialize)]
+struct Forecast {
+    properties: Properties,
+}
+
+#[derive(Deserialize)]
+struct Properties {
+    timeseries: Vec<TimeStep>,
+}
+
+#[derive(Deserialize)]
+struct TimeStep {
+    /// RFC 3339, in UTC.
+    time: String,
+    data: TimeStepData,
+}
+
+#[derive(Deserialize)]
+struct TimeStepData {
+    instant: InstantData,
+    next_1_hours: Option<Period>,
+    next_6_hours: Option<Period>,
+}
+
+#[derive(Deserialize)]
+struct InstantData {
+    details: InstantDetails,
+}
+
+#[derive(Deserialize)]
+struct InstantDetails {
+    air_temperature: f32,
+    air_pressure_at_sea_level: Option<f32>,
+    relative_humidity: Option<f32>,
+    wind_from_direction: Option<f32>,
+    /// In m/s.
+    wind_speed: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct Period {
+    summary: Summary,
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    symbol_code: String,
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    config: &WeatherConfig,
+) -> Result<WeatherReport, AppError> {
+    let response = client
+        .get(&config.met_norway_url)
+        // The API terms ask for at most 4 decimals.
+        .query(&[
+            ("lat", format!("{:.4}", config.latitude)),
+            ("lon", format!("{:.4}", config.longitude)),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| AppError::Upstream(format!("MET Norway request failed: {err}")))?;
+    let forecast = response
+        .json::<Forecast>()
+        .await
+        .map_err(|err| AppError::Upstream(format!("invalid MET Norway response: {err}")))?;
+
+    to_report(forecast.properties.timeseries, &Local)
+}
+
+/// Builds the report from the time steps, the days being those of `tz`.
+fn to_report<Tz>(timeseries: Vec<TimeStep>, tz: &Tz) -> Result<WeatherReport, AppError>
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    let steps = timeseries
+        .into_iter()
+        .map(|step| {
+            let time = DateTime::parse_from_rfc3339(&step.time)
+                .map_err(|err| {
+                    AppError::Upstream(format!("invalid MET Norway time {}: {err}", step.time))
+                })?
+                .with_timezone(tz);
+            Ok((time, step.data))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let Some((now, current)) = steps.first() else {
+        return Err(AppError::Upstream(
+            "MET Norway response has no time steps".to_string(),
+        ));
+    };
+    let details = &current.instant.details;
+    let weathercode = step_weathercode(current);
+
+    let current = Weather {
+        external_temp: details.air_temperature,
+        external_windspeed: details.wind_speed.unwrap_or_default() * 3.6,
+        external_winddirection: details.wind_from_direction.unwrap_or_default(),
+        external_humidity: details.relative_humidity,
+        external_pressure: details.air_pressure_at_sea_level,
+        external_weathercode: weathercode,
+        external_description: weather_description(weathercode),
+        external_time: now.format("%Y-%m-%dT%H:%M").to_string(),
+    };
+
+    let hourly = steps
+        .iter()
+        .take(FORECAST_HOURS as usize)
+        .map(|(time, data)| HourlyForecast {
+            time: time.format("%Y-%m-%dT%H:%M").to_string(),
+            temperature: Some(data.instant.details.air_temperature),
+        })
+        .collect();
+
+    let mut daily: Vec<DailyForecast> = Vec::new();
+    for (time, data) in &steps {
+        let date = time.format("%Y-%m-%d").to_string();
+        let temperature = data.instant.details.air_temperature;
+        if let Some(day) = daily.last_mut().filter(|day| day.date == date) {
+            day.temp_min = day.temp_min.min(temperature);
+            day.temp_max = day.temp_max.max(temperature);
+            // The day is summarized by the forecast starting at noon.
+            if time.hour() == 12 {
+                day.weathercode = step_weathercode(data);
+                day.description = weather_description(day.weathercode);
+            }
+            continue;
+        }
+        if daily.len() == FORECAST_DAYS as usize {
+            break;
+        }
+        let weathercode = step_weathercode(data);
+        daily.push(DailyForecast {
+            date,
+            weathercode,
+            description: weather_description(weathercode),
+            temp_min: temperature,
+            temp_max: temperature,
+            sunrise: None,
+            sunset: None,
+        });
+    }
+
+    Ok(WeatherReport {
+        current,
+        hourly,
+        daily,
+        utc_offset_secs: now.offset().fix().local_minus_utc(),
+    })
+}
+
+fn step_weathercode(data: &TimeStepData) -> u8 {
+    data.next_1_hours
+        .as_ref()
+        .or(data.next_6_hours.as_ref())
+        .map_or(u8::MAX, |period| symbol_to_wmo(&period.summary.symbol_code))
+}
+
+/// Maps a MET Norway weather symbol (e.g. `lightrainshowers_day`) to the
+/// closest WMO weather code.
+fn symbol_to_wmo(symbol: &str) -> u8 {
+    let symbol = symbol.split('_').next().unwrap_or(symbol);
+    if symbol.contains("thunder") {
+        return 95;
+    }
+    match symbol {
+        "clearsky" => 0,
+        "fair" => 1,
+        "partlycloudy" => 2,
+        "cloudy" => 3,
+        "fog" => 45,
+        "lightrain" => 61,
+        "rain" => 63,
+        "heavyrain" => 65,
+        "lightsleet" | "sleet" | "heavysleet" => 66,
+        "lightsnow" => 71,
+        "snow" => 73,
+        "heavysnow" => 75,
+        "lightrainshowers" | "rainshowers" | "heavyrainshowers" => 80,
+        "lightsleetshowers" | "sleetshowers" | "heavysleetshowers" => 80,
+        "lightsnowshowers" | "snowshowers" | "heavysnowshowers" => 85,
+        _ => u8::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn complete_response_is_summarized() {
+        let forecast: Forecast =
+            serde_json::from_str(include_str!("fixtures/met_norway_complete.json")).unwrap();
+
+        let report = to_report(forecast.properties.timeseries, &Utc).unwrap();
+
+        let current = &report.current;
+        assert_eq!(current.external_time, "2026-10-16T10:00");
+        assert_eq!(report.utc_offset_secs, 0);
+        assert_eq!(current.external_temp, 11.0);
+        assert_eq!(current.external_windspeed, 18.0);
+        assert_eq!(current.external_pressure, Some(1012.3));
+        assert_eq!(current.external_weathercode, 3);
+
+        assert_eq!(report.hourly.len(), FORECAST_HOURS as usize);
+        assert_eq!(report.hourly[23].time, "2026-10-17T09:00");
+
+        let days: Vec<_> = report
+            .daily
+            .iter()
+            .map(|day| {
+                (
+                    day.date.as_str(),
+                    day.weathercode,
+                    day.temp_min,
+                    day.temp_max,
+                )
+            })
+            .collect();
+        assert_eq!(
+            days,
+            [
+                ("2026-10-16", 80, 8.5, 13.0),
+                ("2026-10-17", 1, 4.0, 11.0),
+                // Beyond 60 hours, the steps are 6 hours apart.
+                ("2026-10-18", 95, 8.0, 14.0),
+            ]
+        );
+        assert!(report
+            .daily
+            .iter()
+            .all(|day| day.sunrise.is_none() && day.sunset.is_none()));
+    }
+
+    #[test]
+    fn times_are_those_of_the_given_time_zone() {
+        let forecast: Forecast =
+            serde_json::from_str(include_str!("fixtures/met_norway_complete.json")).unwrap();
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+
+        let report = to_report(forecast.properties.timeseries, &tz).unwrap();
+
+        assert_eq!(report.current.external_time, "2026-10-16T12:00");
+        assert_eq!(report.utc_offset_secs, 2 * 3600);
+    }
+
+    #[test]
+    fn empty_response_is_rejected() {
+        assert!(to_report(Vec::new(), &Utc).is_err());
+    }
+
+    #[test]
+    fn symbols_map_to_wmo_codes() {
+        for (symbol, code) in [
+            ("clearsky_day", 0),
+            ("clearsky_polartwilight", 0),
+            ("fair_night", 1),
+            ("partlycloudy_day", 2),
+            ("cloudy", 3),
+            ("fog", 45),
+            ("lightrain", 61),
+            ("heavyrain", 65),
+            ("sleet", 66),
+            ("snow", 73),
+            ("rainshowers_day", 80),
+            ("lightsleetshowers_night", 80),
+            ("heavysnowshowers_day", 85),
+            ("rainandthunder", 95),
+            // Sic, as spelled by the API.
+            ("lightssleetshowersandthunder_day", 95),
+            ("unknown", u8::MAX),
+        ] {
+            assert_eq!(symbol_to_wmo(symbol), code, "{symbol}");
+        }
+    }
+}
@@ -0,0 +1,610 @@
+mod file;
+mod met_norway;
+mod open_meteo;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    Json,
+};
+use chrono::{FixedOffset, Local, NaiveDateTime, TimeZone};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tokio::{sync::Mutex, time::MissedTickBehavior};
+
+use crate::{
+    config::{WeatherConfig, WeatherProviderKind},
+    data::{normalize_timestamp, DataQuery, Page, DB_TIMESTAMP_FORMAT},
+    db,
+    error::AppError,
+    state::AppState,
+};
+
+/// Upper bound of the delay between two attempts when the upstream fails.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of days covered by the daily forecast.
+const FORECAST_DAYS: u32 = 3;
+/// Number of hours covered by the hourly forecast.
+const FORECAST_HOURS: u32 = 24;
+
+#[derive(Serialize, Clone)]
+pub struct Weather {
+    pub external_temp: f32,
+    pub external_windspeed: f32,
+    pub external_winddirection: f32,
+    /// Relative humidity, in %. Missing from readings recorded before it was
+    /// fetched.
+    pub external_humidity: Option<f32>,
+    /// Surface pressure, in hPa. Missing from readings recorded before it
+    /// was fetched.
+    pub external_pressure: Option<f32>,
+    /// WMO weather interpretation code.
+    pub external_weathercode: u8,
+    pub external_description: &'static str,
+    pub external_time: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct HourlyForecast {
+    pub time: String,
+    pub temperature: Option<f32>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DailyForecast {
+    pub date: String,
+    pub weathercode: u8,
+    pub description: &'static str,
+    pub temp_min: f32,
+    pub temp_max: f32,
+    /// Not provided by every provider.
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+}
+
+/// Current weather with the forecast for the next hours and days.
+#[derive(Serialize, Clone)]
+pub struct WeatherReport {
+    #[serde(flatten)]
+    pub current: Weather,
+    pub hourly: Vec<HourlyForecast>,
+    /// Starts with today.
+    pub daily: Vec<DailyForecast>,
+    /// Offset from UTC of the times of the report, which are those of the
+    /// location for open-meteo and of the server for MET Norway.
+    #[serde(skip)]
+    pub utc_offset_secs: i32,
+}
+
+pub type ProviderFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<WeatherReport, AppError>> + Send + 'a>>;
+
+/// Source of weather reports.
+pub trait WeatherProvider: Send + Sync {
+    /// Name used in the configuration and in error messages.
+    fn name(&self) -> &'static str;
+
+    fn fetch(&self) -> ProviderFuture<'_>;
+}
+
+/// Tries each provider in turn until one succeeds.
+pub struct Failover {
+    providers: Vec<Box<dyn WeatherProvider>>,
+}
+
+impl WeatherProvider for Failover {
+    fn name(&self) -> &'static str {
+        "failover"
+    }
+
+    fn fetch(&self) -> ProviderFuture<'_> {
+        Box::pin(async move {
+            let mut errors = Vec::new();
+            for provider in &self.providers {
+                match provider.fetch().await {
+                    Ok(report) => return Ok(report),
+                    Err(err) => errors.push(format!("{}: {err}", provider.name())),
+                }
+            }
+            Err(AppError::Upstream(format!(
+                "all weather providers failed ({})",
+                errors.join("; ")
+            )))
+        })
+    }
+}
+
+/// Builds the providers listed in the configuration, in order.
+pub fn build_provider(
+    config: &WeatherConfig,
+    client: &reqwest::Client,
+) -> Arc<dyn WeatherProvider> {
+    let mut providers: Vec<Box<dyn WeatherProvider>> = config
+        .providers
+        .iter()
+        .map(|kind| -> Box<dyn WeatherProvider> {
+            match kind {
+                WeatherProviderKind::OpenMeteo => {
+                    Box::new(open_meteo::OpenMeteo::new(client.clone(), config.clone()))
+                }
+                WeatherProviderKind::MetNorway => {
+                    Box::new(met_norway::MetNorway::new(client.clone(), config.clone()))
+                }
+                WeatherProviderKind::File => Box::new(file::FileProvider::new(
+                    config.file_path.clone().unwrap_or_default(),
+                )),
+            }
+        })
+        .collect();
+
+    if providers.len() == 1 {
+        Arc::from(providers.remove(0))
+    } else {
+        Arc::new(Failover { providers })
+    }
+}
+
+/// Weather report served by `/external-weather`.
+#[derive(Serialize)]
+pub struct CachedWeather {
+    #[serde(flatten)]
+    pub report: WeatherReport,
+    /// Seconds elapsed since the reading was fetched.
+    pub age_seconds: u64,
+    /// Set when the upstream is failing and an outdated reading is served.
+    pub stale: bool,
+}
+
+/// Caches the last weather reading so that every dashboard poll doesn't hit
+/// the upstream API.
+///
+/// Fetches are serialized by the lock: concurrent requests arriving while
+/// the cache is being refreshed wait for that fetch instead of starting
+/// their own.
+pub struct WeatherCache {
+    provider: Arc<dyn WeatherProvider>,
+    ttl: Duration,
+    retry_interval: Duration,
+    state: Mutex<CacheState>,
+    /// Current weather of the last good reading, behind its own lock so that
+    /// reading it doesn't wait for a fetch in progress.
+    last_current: std::sync::Mutex<Option<Weather>>,
+    fetch_failures: AtomicU64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    last_good: Option<(WeatherReport, Instant)>,
+    last_failure: Option<(String, Instant)>,
+}
+
+impl WeatherCache {
+    pub fn new(provider: Arc<dyn WeatherProvider>, ttl: Duration) -> Self {
+        Self {
+            provider,
+            ttl,
+            retry_interval: ttl.min(MAX_RETRY_INTERVAL),
+            state: Mutex::new(CacheState::default()),
+            last_current: std::sync::Mutex::new(None),
+            fetch_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of fetches that failed since startup, for `/metrics`.
+    pub fn fetch_failures(&self) -> u64 {
+        self.fetch_failures.load(Ordering::Relaxed)
+    }
+
+    /// Returns the last good reading without refreshing it, nor waiting for
+    /// a refresh in progress.
+    pub fn last_reading(&self) -> Option<Weather> {
+        self.last_current.lock().unwrap().clone()
+    }
+
+    /// Returns the cached reading, fetching a new one first if it has
+    /// expired.
+    ///
+    /// When the provider fails the last good reading is returned, marked as
+    /// stale; an error is only returned if there is no reading at all.
+    pub async fn get(&self) -> Result<CachedWeather, AppError> {
+        let mut state = self.state.lock().await;
+
+        let fresh =
+            matches!(&state.last_good, Some((_, fetched_at)) if fetched_at.elapsed() < self.ttl);
+        let failed_recently = matches!(
+            &state.last_failure,
+            Some((_, failed_at)) if failed_at.elapsed() < self.retry_interval
+        );
+
+        if !fresh && !failed_recently {
+            match self.provider.fetch().await {
+                Ok(weather) => {
+                    *self.last_current.lock().unwrap() = Some(weather.current.clone());
+                    state.last_good = Some((weather, Instant::now()));
+                    state.last_failure = None;
+                }
+                Err(err) => {
+                    eprintln!("error: cannot refresh weather: {err}");
+                    self.fetch_failures.fetch_add(1, Ordering::Relaxed);
+                    state.last_failure = Some((err.to_string(), Instant::now()));
+                }
+            }
+        }
+
+        match (&state.last_good, &state.last_failure) {
+            (Some((report, fetched_at)), _) => {
+                let age = fetched_at.elapsed();
+                Ok(CachedWeather {
+                    report: report.clone(),
+                    age_seconds: age.as_secs(),
+                    stale: age >= self.ttl,
+                })
+            }
+            (None, Some((err, _))) => Err(AppError::Upstream(err.clone())),
+            (None, None) => Err(AppError::Upstream("no weather reading yet".to_string())),
+        }
+    }
+}
+
+pub async fn external_weather(
+    State(state): State<AppState>,
+) -> Result<Json<CachedWeather>, AppError> {
+    let weather = state.weather.get().await?;
+    Ok(Json(weather))
+}
+
+pub async fn external_weather_history(
+    State(state): State<AppState>,
+    query: Result<Query<DataQuery>, QueryRejection>,
+) -> Result<Json<Page<Weather>>, AppError> {
+    let Query(query) = query?;
+    let params = query.parse()?;
+
+    let limit = params.limit;
+    let rows = db::run(&state.db, move |conn| {
+        query_history(conn, params.from, params.to, params.before, limit + 1)
+    })
+    .await?;
+
+    Ok(Json(Page::from_rows(rows, limit, |weather| {
+        weather.external_time.clone()
+    })))
+}
+
+/// Starts the task recording the outdoor weather in the `ExternalWeather`
+/// table every `poll_interval_secs`.
+pub fn spawn_poller(state: AppState) {
+    let interval = state.config.weather.poll_interval_secs;
+    if interval == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = record_weather(&state).await {
+                eprintln!("error: cannot record weather: {err}");
+            }
+        }
+    });
+}
+
+/// Stores the current reading, going through the cache so that the poller
+/// and the dashboard share upstream requests.
+async fn record_weather(state: &AppState) -> Result<(), AppError> {
+    let reading = state.weather.get().await?;
+    if reading.stale {
+        return Ok(());
+    }
+
+    let utc_timestamps = state.config.database.utc_timestamps;
+    db::run(&state.writer, move |conn| {
+        insert_weather(conn, &reading.report, utc_timestamps)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Inserts the current reading of `report`, unless one with the same
+/// timestamp is already stored.
+fn insert_weather(
+    conn: &Connection,
+    report: &WeatherReport,
+    utc_timestamps: bool,
+) -> rusqlite::Result<()> {
+    let weather = &report.current;
+    // Providers only update their readings every 15 minutes or so: the same
+    // one is typically fetched several times.
+    let timestamp =
+        recorded_timestamp(report, utc_timestamps).unwrap_or_else(|| weather.external_time.clone());
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO ExternalWeather \
+         (timestamp, temperature, windspeed, winddirection, humidity, pressure, weather_code) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?
+    .execute(params![
+        timestamp,
+        weather.external_temp,
+        weather.external_windspeed,
+        weather.external_winddirection,
+        weather.external_humidity,
+        weather.external_pressure,
+        weather.external_weathercode,
+    ])?;
+    Ok(())
+}
+
+/// Converts the time of the current reading of `report` to the time zone of
+/// the database timestamps: UTC with `database.utc_timestamps`, the one of
+/// the server otherwise, like the sensor rows.
+fn recorded_timestamp(report: &WeatherReport, utc_timestamps: bool) -> Option<String> {
+    let time = normalize_timestamp(&report.current.external_time)?;
+    let time = NaiveDateTime::parse_from_str(&time, DB_TIMESTAMP_FORMAT).ok()?;
+    let time = FixedOffset::east_opt(report.utc_offset_secs)?
+        .from_local_datetime(&time)
+        .single()?
+        .to_utc();
+    let time = if utc_timestamps {
+        time.naive_utc()
+    } else {
+        time.with_timezone(&Local).naive_local()
+    };
+    Some(time.format(DB_TIMESTAMP_FORMAT).to_string())
+}
+
+/// Returns up to `limit` recorded readings in the given time range, newest
+/// first.
+fn query_history(
+    conn: &Connection,
+    from: Option<String>,
+    to: Option<String>,
+    before: Option<String>,
+    limit: u32,
+) -> rusqlite::Result<Vec<Weather>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT timestamp, temperature, windspeed, winddirection, humidity, pressure, weather_code \
+         FROM ExternalWeather \
+         WHERE (?1 IS NULL OR timestamp >= ?1) \
+           AND (?2 IS NULL OR timestamp <= ?2) \
+           AND (?3 IS NULL OR timestamp < ?3) \
+         ORDER BY timestamp DESC \
+         LIMIT ?4",
+    )?;
+
+    let rows = stmt.query_map(params![from, to, before, limit], |row| {
+        let weathercode = row.get(6)?;
+        Ok(Weather {
+            external_time: row.get(0)?,
+            external_temp: row.get(1)?,
+            external_windspeed: row.get(2)?,
+            external_winddirection: row.get(3)?,
+            external_humidity: row.get(4)?,
+            external_pressure: row.get(5)?,
+            external_weathercode: weathercode,
+            external_description: weather_description(weathercode),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Describes a WMO weather interpretation code, as used by open-meteo.
+///
+/// Other providers map their own codes to the WMO ones.
+pub fn weather_description(code: u8) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 => "Slight rain",
+        63 => "Rain",
+        65 => "Heavy rain",
+        66 | 67 => "Freezing rain",
+        71 => "Slight snow",
+        73 => "Snow",
+        75 => "Heavy snow",
+        77 => "Snow grains",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+
+    use super::*;
+
+    /// Local stand-in for open-meteo counting the requests it receives.
+    struct MockUpstream {
+        hits: Arc<AtomicUsize>,
+        failing: Arc<AtomicBool>,
+        config: WeatherConfig,
+    }
+
+    impl MockUpstream {
+        async fn start() -> Self {
+            let hits = Arc::new(AtomicUsize::new(0));
+            let failing = Arc::new(AtomicBool::new(false));
+
+            let app = Router::new().route(
+                "/v1/forecast",
+                get({
+                    let hits = hits.clone();
+                    let failing = failing.clone();
+                    move || async move {
+                        hits.fetch_add(1, Ordering::SeqCst);
+                        // Leaves time for concurrent requests to pile up.
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        if failing.load(Ordering::SeqCst) {
+                            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                        }
+                        Json(serde_json::json!({
+                            "utc_offset_seconds": 7200,
+                            "current": {
+                                "time": "2026-10-16T12:00",
+                                "temperature_2m": 12.5,
+                                "relative_humidity_2m": 81.0,
+                                "surface_pressure": 1012.3,
+                                "wind_speed_10m": 8.0,
+                                "wind_direction_10m": 270.0,
+                                "weather_code": 3,
+                            },
+                            "hourly": {
+                                "time": ["2026-10-16T12:00", "2026-10-16T13:00"],
+                                "temperature_2m": [12.5, null],
+                            },
+                            "daily": {
+                                "time": ["2026-10-16", "2026-10-17", "2026-10-18"],
+                                "weather_code": [3, 61, 0],
+                                "temperature_2m_min": [8.1, 7.4, 6.0],
+                                "temperature_2m_max": [14.2, 12.9, 15.3],
+                                "sunrise": ["2026-10-16T08:11", "2026-10-17T08:12", "2026-10-18T08:14"],
+                                "sunset": ["2026-10-16T18:59", "2026-10-17T18:57", "2026-10-18T18:55"],
+                            },
+                        }))
+                        .into_response()
+                    }
+                }),
+            );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            Self {
+                hits,
+                failing,
+                config: WeatherConfig {
+                    open_meteo_url: format!("http://{addr}/v1/forecast"),
+                    ..WeatherConfig::default()
+                },
+            }
+        }
+
+        fn provider(&self) -> Box<dyn WeatherProvider> {
+            Box::new(open_meteo::OpenMeteo::new(
+                reqwest::Client::new(),
+                self.config.clone(),
+            ))
+        }
+
+        fn cache(&self, ttl: Duration) -> WeatherCache {
+            WeatherCache::new(Arc::from(self.provider()), ttl)
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_cached_reading_within_ttl() {
+        let upstream = MockUpstream::start().await;
+        let cache = upstream.cache(Duration::from_secs(60));
+
+        let first = cache.get().await.unwrap();
+        let second = cache.get().await.unwrap();
+
+        assert_eq!(upstream.hits.load(Ordering::SeqCst), 1);
+        assert_eq!(first.report.current.external_temp, 12.5);
+        assert_eq!(first.report.current.external_description, "Overcast");
+        assert_eq!(first.report.daily.len(), 3);
+        assert_eq!(first.report.daily[1].description, "Slight rain");
+        assert_eq!(first.report.hourly[1].temperature, None);
+        assert_eq!(second.report.current.external_time, "2026-10-16T12:00");
+        assert!(!second.stale);
+    }
+
+    #[tokio::test]
+    async fn coalesces_concurrent_fetches() {
+        let upstream = Arc::new(MockUpstream::start().await);
+        let cache = Arc::new(upstream.cache(Duration::from_secs(60)));
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            let cache = cache.clone();
+            tasks.spawn(async move { cache.get().await.is_ok() });
+        }
+
+        assert!(tasks.join_all().await.into_iter().all(|ok| ok));
+        assert_eq!(upstream.hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_reading_when_upstream_fails() {
+        let upstream = MockUpstream::start().await;
+        let cache = upstream.cache(Duration::ZERO);
+
+        cache.get().await.unwrap();
+        upstream.failing.store(true, Ordering::SeqCst);
+        let stale = cache.get().await.unwrap();
+
+        assert_eq!(upstream.hits.load(Ordering::SeqCst), 2);
+        assert!(stale.stale);
+        assert_eq!(stale.report.current.external_temp, 12.5);
+    }
+
+    #[tokio::test]
+    async fn fails_without_any_reading() {
+        let upstream = MockUpstream::start().await;
+        upstream.failing.store(true, Ordering::SeqCst);
+        let cache = upstream.cache(Duration::from_secs(60));
+
+        let err = cache.get().await.err().unwrap();
+
+        assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_provider() {
+        let broken = MockUpstream::start().await;
+        broken.failing.store(true, Ordering::SeqCst);
+        let working = MockUpstream::start().await;
+        let failover = Failover {
+            providers: vec![broken.provider(), working.provider()],
+        };
+
+        let report = failover.fetch().await.unwrap();
+
+        assert_eq!(broken.hits.load(Ordering::SeqCst), 1);
+        assert_eq!(working.hits.load(Ordering::SeqCst), 1);
+        assert_eq!(report.current.external_temp, 12.5);
+    }
+
+    #[test]
+    fn recorded_timestamps_follow_the_database_time_zone() {
+        // 12:00 in Paris, at UTC+2 in October.
+        let report =
+            open_meteo::parse_response(include_bytes!("fixtures/open_meteo.json")).unwrap();
+        assert_eq!(report.current.external_time, "2026-10-16T12:00");
+
+        assert_eq!(
+            recorded_timestamp(&report, true).as_deref(),
+            Some("2026-10-16 10:00:00")
+        );
+        let local = Local
+            .from_utc_datetime(&"2026-10-16T10:00:00".parse().unwrap())
+            .format(DB_TIMESTAMP_FORMAT)
+            .to_string();
+        assert_eq!(recorded_timestamp(&report, false), Some(local));
+    }
+}
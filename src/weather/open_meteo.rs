@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+use crate::{config::WeatherConfig, error::AppError};
+
+use super::{
+    weather_description, DailyForecast, HourlyForecast, ProviderFuture, Weather, WeatherProvider,
+    WeatherReport, FORECAST_DAYS, FORECAST_HOURS,
+};
+
+/// [open-meteo](https://open-meteo.com/) forecast API.
+pub struct OpenMeteo {
+    client: reqwest::Client,
+    config: WeatherConfig,
+}
+
+impl OpenMeteo {
+    pub fn new(client: reqwest::Client, config: WeatherConfig) -> Self {
+        Self { client, config }
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    fn fetch(&self) -> ProviderFuture<'_> {
+        Box::pin(fetch(&self.client, &self.config))
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoResponse {
+    /// Of the time zone of the location, the one of all the times with
+    /// `timezone=auto`.
+    utc_offset_seconds: i32,
+    current: OpenMeteoCurrent,
+    hourly: OpenMeteoHourly,
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoCurrent {
+    time: String,
+    temperature_2m: f32,
+    relative_humidity_2m: f32,
+    surface_pressure: f32,
+    wind_speed_10m: f32,
+    wind_direction_10m: f32,
+    weather_code: u8,
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoHourly {
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f32>>,
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoDaily {
+    time: Vec<String>,
+    weather_code: Vec<u8>,
+    temperature_2m_min: Vec<f32>,
+    temperature_2m_max: Vec<f32>,
+    sunrise: Vec<String>,
+    sunset: Vec<String>,
+}
+
+/// Parses a response of the forecast API, as requested by [`OpenMeteo`].
+pub fn parse_response(body: &[u8]) -> serde_json::Result<WeatherReport> {
+    serde_json::from_slice::<OpenMeteoResponse>(body).map(WeatherReport::from)
+}
+
+impl From<OpenMeteoResponse> for WeatherReport {
+    fn from(response: OpenMeteoResponse) -> Self {
+        let OpenMeteoResponse {
+            utc_offset_seconds,
+            current,
+            hourly,
+            daily,
+        } = response;
+
+        let hourly = hourly
+            .time
+            .into_iter()
+            .zip(hourly.temperature_2m)
+            .map(|(time, temperature)| HourlyForecast { time, temperature })
+            .collect();
+
+        let daily = daily
+            .time
+            .into_iter()
+            .zip(daily.weather_code)
+            .zip(
+                daily
+                    .temperature_2m_min
+                    .into_iter()
+                    .zip(daily.temperature_2m_max),
+            )
+            .zip(daily.sunrise.into_iter().zip(daily.sunset))
+            .map(
+                |(((date, weathercode), (temp_min, temp_max)), (sunrise, sunset))| DailyForecast {
+                    date,
+                    weathercode,
+                    description: weather_description(weathercode),
+                    temp_min,
+                    temp_max,
+                    sunrise: Some(sunrise),
+                    sunset: Some(sunset),
+                },
+            )
+            .collect();
+
+        WeatherReport {
+            current: Weather {
+                external_temp: current.temperature_2m,
+                external_windspeed: current.wind_speed_10m,
+                external_winddirection: current.wind_direction_10m,
+                external_humidity: Some(current.relative_humidity_2m),
+                external_pressure: Some(current.surface_pressure),
+                external_weathercode: current.weather_code,
+                external_description: weather_description(current.weather_code),
+                external_time: current.time,
+            },
+            hourly,
+            daily,
+            utc_offset_secs: utc_offset_seconds,
+        }
+    }
+}
+
+/// Fetches the current weather and the forecast from open-meteo.
+async fn fetch(
+    client: &reqwest::Client,
+    config: &WeatherConfig,
+) -> Result<WeatherReport, AppError> {
+    let response = client
+        .get(&config.open_meteo_url)
+        .query(&[
+            ("latitude", config.latitude.to_string()),
+            ("longitude", config.longitude.to_string()),
+            (
+                "current",
+                "temperature_2m,relative_humidity_2m,surface_pressure,\
+                 wind_speed_10m,wind_direction_10m,weather_code"
+                    .to_string(),
+            ),
+            ("hourly", "temperature_2m".to_string()),
+            (
+                "daily",
+                "weather_code,temperature_2m_min,temperature_2m_max,sunrise,sunset".to_string(),
+            ),
+            ("forecast_days", FORECAST_DAYS.to_string()),
+            ("forecast_hours", FORECAST_HOURS.to_string()),
+            ("timezone", "auto".to_string()),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| AppError::Upstream(format!("open-meteo request failed: {err}")))?;
+    let response = response
+        .json::<OpenMeteoResponse>()
+        .await
+        .map_err(|err| AppError::Upstream(format!("invalid open-meteo response: {err}")))?;
+
+    Ok(response.into())
+}